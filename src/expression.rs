@@ -1,5 +1,21 @@
 pub mod arithmetic;
+mod bigint;
 pub mod boolean;
+pub mod builder;
 mod common;
+mod decimal;
+pub mod json;
+mod number;
+pub mod optimize;
+mod rational;
+pub mod rule;
+mod truth;
+pub mod typing;
+mod value;
 
-pub use common::Reducible;
+pub use common::{Context, Reducible};
+pub use decimal::{Precision, Rounding};
+pub use number::{Arithmetic, FloatPolicy, Number};
+pub use rational::Rational;
+pub use truth::{PartiallyReducible, Truth};
+pub use value::Value;
@@ -1,30 +1,251 @@
 mod expression;
 mod factor;
+mod literal;
+mod residual;
 
+use std::fmt;
+
+use nom::branch::alt;
 use nom::bytes::complete::tag;
-use nom::character::complete::{alpha1, space0};
-use nom::combinator::map;
-use nom::sequence::{delimited, pair};
+use nom::character::complete::{digit1, space0, space1};
+use nom::combinator::{map, map_res, opt, value, verify};
+use nom::multi::separated_nonempty_list;
+use nom::sequence::{delimited, pair, preceded, terminated, tuple};
 use nom::IResult;
 
 pub use self::expression::Expression;
-use crate::expression::common::Expression as ExpressionTrait;
+pub(crate) use self::expression::{ExpressionOperator, Term, TermOperator};
+pub(crate) use self::factor::Factor;
+pub use self::literal::malformed_number;
+pub use self::residual::Residual;
+use crate::expression::boolean::Expression as BooleanExpression;
+use crate::expression::common::{
+    identifier, spanned, token, Context, Expression as ExpressionTrait, Span,
+};
+use crate::expression::decimal::{Precision, Rounding};
+use crate::expression::optimize::{Operand, Optimize};
+
+/// Names outputs can't have, since they are the other keys of responses.
+pub const RESERVED_OUTPUTS: [&str; 4] = ["H", "DEFAULT", "EXACT", "DEVIATION"];
+
+/// An arithmetic rule, such as `H = M => K[2] = D * 2`.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Formula {
+    pub matcher: Matcher,
+    /// An extra condition, as in `H = P && D > 100 => K = D * 0.9`.
+    pub guard: Option<BooleanExpression>,
+    /// Usually just K, but a formula may assign several outputs, as in
+    /// `H = P => net = D, tax = net * 0.19`.
+    pub outputs: Vec<Output>,
+}
+
+/// A single assignment of a formula, such as `K[2] = D * 2`.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Output {
+    pub name: String,
+    /// Location of the name in the source.
+    pub span: Span,
+    /// The precision the result is rounded to, if the rule declares one.
+    pub precision: Option<Precision>,
+    pub expression: Expression,
+}
+
+/// The values of H a formula applies to.
+#[derive(Clone, PartialEq, Debug)]
+pub enum Matcher {
+    /// Any of the labels, such as `M | P`.
+    Labels(Vec<String>),
+    /// Any label at all, `*`. Used when no formula names the label.
+    Wildcard,
+}
+
+impl Matcher {
+    pub fn matches(&self, label: &str) -> bool {
+        match self {
+            Self::Labels(labels) => labels.iter().any(|item| item == label),
+            Self::Wildcard => true,
+        }
+    }
+}
+
+impl Formula {
+    /// The first output which assigns a name already assigned by an earlier
+    /// one, along with its index. Responses hold a single value per name,
+    /// so formulas may assign each name once only.
+    pub fn duplicate_output(&self) -> Option<(usize, &Output)> {
+        self.outputs.iter().enumerate().find(|(index, output)| {
+            self.outputs[..*index]
+                .iter()
+                .any(|earlier| earlier.name == output.name)
+        })
+    }
+}
+
+/// Guards which always pass are dropped, since they are no guards at all.
+impl Optimize for Formula {
+    fn optimize(self, context: &Context) -> Self {
+        let guard = self
+            .guard
+            .map(|guard| guard.optimize(context))
+            .filter(|guard| guard.constant() != Some(true.into()));
+        let outputs = self
+            .outputs
+            .into_iter()
+            .map(|output| Output {
+                expression: output.expression.optimize(context),
+                ..output
+            })
+            .collect();
+
+        Self {
+            matcher: self.matcher,
+            guard,
+            outputs,
+        }
+    }
+}
+
+impl fmt::Display for Formula {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        write!(formatter, "H = {}", self.matcher)?;
+
+        if let Some(guard) = &self.guard {
+            write!(formatter, " && {}", guard)?;
+        }
+
+        let outputs: Vec<String> =
+            self.outputs.iter().map(ToString::to_string).collect();
+
+        write!(formatter, " => {}", outputs.join(", "))
+    }
+}
+
+impl fmt::Display for Output {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str(&self.name)?;
+
+        if let Some(precision) = &self.precision {
+            write!(formatter, "[{}]", precision)?;
+        }
+
+        write!(formatter, " = {}", self.expression)
+    }
+}
+
+impl fmt::Display for Matcher {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Labels(labels) => formatter.write_str(&labels.join(" | ")),
+            Self::Wildcard => formatter.write_str("*"),
+        }
+    }
+}
 
 /// Parses the whole arithmetic expression, such as `H = M => K = D * 2`.
-pub fn parse(input: &str) -> IResult<&str, (Expression, String)> {
-    let parser = pair(parse_matcher_clause, Expression::parse);
+pub fn parse(input: &str) -> IResult<&str, Formula> {
+    let outputs = separated_nonempty_list(tag(","), parse_output);
+    let parser = pair(parse_matcher_clause, outputs);
+
+    map(parser, |((matcher, guard), outputs)| Formula {
+        matcher,
+        guard,
+        outputs,
+    })(input)
+}
+
+/// Parses a named constant, such as `const RATE_P = 25.5`, returning its
+/// name and the expression which defines it.
+pub fn parse_constant(input: &str) -> IResult<&str, (String, Expression)> {
+    let name = delimited(pair(token("const"), space1), identifier, space0);
+
+    pair(
+        map(name, String::from),
+        preceded(tag("="), Expression::parse),
+    )(input)
+}
+
+/// Parses a matcher clause, such as `H = M =>` and returns the values of
+/// H corresponding to the clause (M in the case of the example).
+///
+/// The clause may list alternatives, as in `H = M | P =>`, or match any
+/// value, as in `H = * =>`. It may also be guarded by a boolean
+/// expression, as in `H = P && D > 100 =>`. The guard is the rest of the
+/// clause, so `H = P && D > 100 || A =>` is guarded by `D > 100 || A`.
+fn parse_matcher_clause(
+    input: &str,
+) -> IResult<&str, (Matcher, Option<BooleanExpression>)> {
+    let wildcard =
+        value(Matcher::Wildcard, delimited(space0, tag("*"), space0));
+    let labels =
+        map(separated_nonempty_list(tag("|"), variable), Matcher::Labels);
+    let guard =
+        preceded(alt((tag("&&"), token("and"))), BooleanExpression::parse);
+
+    delimited(
+        tag("H = "),
+        pair(alt((wildcard, labels)), opt(guard)),
+        tag("=>"),
+    )(input)
+}
 
-    map(parser, |(string, expression)| (expression, string))(input)
+/// Parses an output, such as `K = D * 2`, optionally along with its
+/// precision, as in `K[2, half_up] = D * 2`. H can't be assigned, nor
+/// can any other reserved name.
+fn parse_output(input: &str) -> IResult<&str, Output> {
+    let name = delimited(space0, spanned(identifier), space0);
+    let name = verify(name, |(name, _): &(&str, Span)| {
+        !RESERVED_OUTPUTS.contains(name)
+    });
+    let parser = tuple((
+        name,
+        terminated(opt(precision), pair(space0, tag("="))),
+        Expression::parse,
+    ));
+
+    map(parser, |((name, span), precision, expression)| Output {
+        name: name.into(),
+        span,
+        precision,
+        expression,
+    })(input)
+}
+
+/// Parses a precision, such as `[2]` or `[2, half_up]`. Ties are rounded
+/// to even unless stated otherwise.
+fn precision(input: &str) -> IResult<&str, Precision> {
+    delimited(
+        terminated(tag("["), space0),
+        precision_digits,
+        preceded(space0, tag("]")),
+    )(input)
 }
 
-/// Parses a matcher clause, such as `H = M => K =` and returns the
-/// value of H corresponding to the clause (M in the case of the example).
-fn parse_matcher_clause(input: &str) -> IResult<&str, String> {
-    delimited(tag("H = "), variable, tag("=> K = "))(input)
+/// Parses a precision directive, such as `precision 2, half_up`, which
+/// sets the precision of the rules which don't declare their own.
+pub fn parse_precision(input: &str) -> IResult<&str, Precision> {
+    delimited(pair(token("precision"), space1), precision_digits, space0)(input)
+}
+
+/// Parses the digits of a precision and, optionally, the rounding, such
+/// as `2, half_up`.
+fn precision_digits(input: &str) -> IResult<&str, Precision> {
+    let digits = map_res(digit1, str::parse);
+    let rounding = alt((
+        value(Rounding::HalfEven, tag("half_even")),
+        value(Rounding::HalfUp, tag("half_up")),
+    ));
+    let rounding = preceded(tuple((space0, tag(","), space0)), rounding);
+
+    map(pair(digits, opt(rounding)), |(digits, rounding)| {
+        Precision {
+            digits,
+            rounding: rounding.unwrap_or_default(),
+        }
+    })(input)
 }
 
 fn variable(input: &str) -> IResult<&str, String> {
-    map(delimited(space0, alpha1, space0), |string: &str| {
+    map(delimited(space0, identifier, space0), |string: &str| {
         string.into()
     })(input)
 }
@@ -38,20 +259,131 @@ mod test {
         let actual =
             parse_matcher_clause("H = M => K = D * 2 / A * B - 5").unwrap();
 
-        assert_eq!(("D * 2 / A * B - 5", "M".into()), actual);
+        let matcher = Matcher::Labels(vec!["M".into()]);
+
+        assert_eq!((" K = D * 2 / A * B - 5", (matcher, None)), actual);
+    }
+
+    #[test]
+    fn test_alternatives_parsing() {
+        let actual = parse_matcher_clause("H = M | P|T => K = D").unwrap();
+        let matcher = Matcher::Labels(vec!["M".into(), "P".into(), "T".into()]);
+
+        assert!(matcher.matches("P"));
+        assert!(!matcher.matches("X"));
+        assert_eq!((" K = D", (matcher, None)), actual);
+
+        let actual = parse_matcher_clause("H = * => K = D").unwrap();
+
+        assert_eq!((" K = D", (Matcher::Wildcard, None)), actual);
+        assert!(parse_matcher_clause("H = M || P => K = D").is_err());
+    }
+
+    #[test]
+    fn test_guard_parsing() {
+        let (rest, (matcher, guard)) =
+            parse_matcher_clause("H = P && D > 100 || A => K = D").unwrap();
+        let expected = BooleanExpression::parse("D > 100 || A").unwrap().1;
+
+        assert_eq!(" K = D", rest);
+        assert_eq!(Matcher::Labels(vec!["P".into()]), matcher);
+        assert_eq!(Some(expected), guard);
+
+        let (_, (matcher, guard)) =
+            parse_matcher_clause("H = * and !A => K = D").unwrap();
+
+        assert_eq!(Matcher::Wildcard, matcher);
+        assert!(guard.is_some());
+    }
+
+    #[test]
+    fn test_output_parsing() {
+        let (rest, output) = parse_output(" K[2, half_up] = D").unwrap();
+        let precision = Precision {
+            digits: 2,
+            rounding: Rounding::HalfUp,
+        };
+
+        assert_eq!("", rest);
+        assert_eq!("K", output.name);
+        assert_eq!(Some(precision), output.precision);
+
+        let (_, output) = parse_output("net[ 4 ]= D").unwrap();
+
+        assert_eq!(Some(4), output.precision.map(|precision| precision.digits));
+        assert!(parse_output("K[2, half_odd] = D").is_err());
+        assert!(parse_output("H = D").is_err());
+        assert!(parse_output("DEFAULT = D").is_err());
+    }
+
+    #[test]
+    fn test_constant_parsing() {
+        let (rest, (name, _)) = parse_constant("const RATE_P = 25.5").unwrap();
+
+        assert_eq!(("", "RATE_P"), (rest, name.as_str()));
+        assert!(parse_constant("constant RATE_P = 25.5").is_err());
+        assert!(parse_constant("const = 25.5").is_err());
+    }
+
+    #[test]
+    fn test_precision_parsing() {
+        let (rest, precision) = parse_precision("PRECISION 2,half_up").unwrap();
+        let expected = Precision {
+            digits: 2,
+            rounding: Rounding::HalfUp,
+        };
+
+        assert_eq!(("", expected), (rest, precision));
+        assert_eq!(
+            Ok(("", 4)),
+            parse_precision("precision 4 ")
+                .map(|(rest, precision)| (rest, precision.digits))
+        );
+        assert!(parse_precision("precision2").is_err());
     }
 
     #[test]
     fn test_integration_parser() {
-        use crate::expression::common::Reducible;
-        use std::collections::HashMap;
+        use crate::expression::common::{Context, Reducible};
+        use crate::expression::value::Value;
 
         let input = "H = M => K = 21 * 4 / 2 - 10";
 
         let parsed = parse(input).unwrap().1;
-        let result = parsed.0.reduce(&HashMap::new()).unwrap();
+        let result: Value = parsed.outputs[0]
+            .expression
+            .reduce(&Context::default())
+            .unwrap();
+
+        assert_eq!(result, 32.0.into());
+        assert_eq!(parsed.matcher, Matcher::Labels(vec!["M".into()]));
+
+        let input = "H = P => net = D, tax[2] = net * 0.19, gross = net + tax";
+        let (rest, parsed) = parse(input).unwrap();
+        let names: Vec<&str> = parsed
+            .outputs
+            .iter()
+            .map(|output| output.name.as_str())
+            .collect();
+
+        assert_eq!("", rest);
+        assert_eq!(vec!["net", "tax", "gross"], names);
+    }
+
+    #[test]
+    fn test_formula_printing() {
+        for &(source, expected) in &[
+            ("H = M=>K=D*(2)", "H = M => K = D * 2"),
+            ("H = M|P and !A => K[ 2 ]=D", "H = M | P && !A => K[2] = D"),
+            (
+                "H = * => net = D,tax[2,half_up]=net×0.19",
+                "H = * => net = D, tax[2, half_up] = net * 0.19",
+            ),
+        ] {
+            let (rest, formula) = parse(source).unwrap();
 
-        assert_eq!(result, 32.0);
-        assert_eq!(parsed.1, "M");
+            assert_eq!("", rest);
+            assert_eq!(expected, formula.to_string());
+        }
     }
 }
@@ -1,57 +1,283 @@
 /// Expressions consist of terms, which are added and subtracted.
 /// Terms consist of factors, which are multiplied and divided.
+use std::fmt;
 use std::str::FromStr;
 
+use anyhow::Result;
+
 use super::factor::Factor;
+use super::residual::Residual;
 use crate::expression;
+use crate::expression::common::{
+    Canonical, Context, Expression as ExpressionTrait, ExpressionWithOperator,
+    Operator, Precedence, Reducible, Variables,
+};
+use crate::expression::number::Number;
+use crate::expression::optimize::{fold_numbers, is_integer, Identities};
+use crate::expression::rational::Rational;
+use crate::expression::value::Value;
 
 // Represents a term, which can consists of one, or multiple factors which
 // can be divided or multiplied.
-expression!(Term<f64, Factor> | TermOperator: Multiply => *, Divide => /);
+expression!(Term<Value, Factor> | TermOperator(Number):
+Multiply("*", "×") => |lhs, rhs, context| {
+    let product = lhs.as_number()?.checked_mul(rhs.as_number()?)?;
+
+    context.floats.check(product).map(Value::Number)
+},
+Divide("/", "÷") => |lhs, rhs, context| {
+    let divisor = context.floats.check_divisor(rhs.as_number()?)?;
+    let quotient = lhs.as_number()?.checked_div(divisor)?;
+
+    context.floats.check(quotient).map(Value::Number)
+});
 
 // Represents an expression, which can consists of one, or multiple terms
 // which can be added or subtracted.
-expression!(Expression<f64, Term> | ExpressionOperator: Add => +, Subtract => -);
+expression!(Expression<Value, Term> | ExpressionOperator(Number):
+Add("+") => |lhs, rhs, context| {
+    let sum = lhs.as_number()?.checked_add(rhs.as_number()?)?;
+
+    context.floats.check(sum).map(Value::Number)
+},
+Subtract("-") => |lhs, rhs, context| {
+    let difference = lhs.as_number()?.checked_sub(rhs.as_number()?)?;
+
+    context.floats.check(difference).map(Value::Number)
+});
+
+impl Precedence for TermOperator {
+    const PRECEDENCE: u8 = 2;
+}
+
+impl Precedence for ExpressionOperator {
+    const PRECEDENCE: u8 = 1;
+}
+
+/// Operators which can be applied to rationals, without any rounding.
+pub trait ExactOperator {
+    fn apply_exact(&self, lhs: &Rational, rhs: &Rational) -> Result<Rational>;
+}
+
+impl ExactOperator for TermOperator {
+    fn apply_exact(&self, lhs: &Rational, rhs: &Rational) -> Result<Rational> {
+        match self {
+            Self::Multiply => lhs.multiply(rhs),
+            Self::Divide => lhs.divide(rhs),
+        }
+    }
+}
+
+impl ExactOperator for ExpressionOperator {
+    fn apply_exact(&self, lhs: &Rational, rhs: &Rational) -> Result<Rational> {
+        match self {
+            Self::Add => lhs.add(rhs),
+            Self::Subtract => lhs.subtract(rhs),
+        }
+    }
+}
+
+impl Identities for TermOperator {
+    fn fold(&self, lhs: Value, rhs: Value, context: &Context) -> Option<Value> {
+        match self {
+            Self::Multiply => {
+                fold_numbers(lhs, rhs, context, Number::checked_mul)
+            },
+            Self::Divide => {
+                fold_numbers(lhs, rhs, context, Number::checked_div)
+            },
+        }
+    }
+
+    fn is_right_identity(&self, constant: &Value) -> bool {
+        is_integer(constant, 1)
+    }
+
+    fn is_left_identity(&self, constant: &Value) -> bool {
+        *self == Self::Multiply && is_integer(constant, 1)
+    }
+}
+
+impl Identities for ExpressionOperator {
+    fn fold(&self, lhs: Value, rhs: Value, context: &Context) -> Option<Value> {
+        match self {
+            Self::Add => fold_numbers(lhs, rhs, context, Number::checked_add),
+            Self::Subtract => {
+                fold_numbers(lhs, rhs, context, Number::checked_sub)
+            },
+        }
+    }
+
+    fn is_right_identity(&self, constant: &Value) -> bool {
+        is_integer(constant, 0)
+    }
+
+    fn is_left_identity(&self, constant: &Value) -> bool {
+        *self == Self::Add && is_integer(constant, 0)
+    }
+}
+
+/// Operators which can be applied to residuals, folding the known parts.
+pub trait ResidualOperator {
+    fn apply_residual(
+        &self,
+        lhs: Residual,
+        rhs: Residual,
+        context: &Context,
+    ) -> Result<Residual>;
+}
+
+impl ResidualOperator for TermOperator {
+    fn apply_residual(
+        &self,
+        lhs: Residual,
+        rhs: Residual,
+        context: &Context,
+    ) -> Result<Residual> {
+        lhs.multiply(rhs, *self == Self::Divide, context)
+    }
+}
+
+impl ResidualOperator for ExpressionOperator {
+    fn apply_residual(
+        &self,
+        lhs: Residual,
+        rhs: Residual,
+        context: &Context,
+    ) -> Result<Residual> {
+        lhs.add(rhs, *self == Self::Subtract, context)
+    }
+}
+
+impl<T, O> Reducible<Residual> for ExpressionWithOperator<T, O>
+where
+    T: ExpressionTrait + Reducible<Residual> + Canonical + Variables,
+    O: Operator + ResidualOperator + Precedence + fmt::Display,
+{
+    fn reduce(&self, context: &Context) -> Result<Residual> {
+        let init = self.head.reduce(context)?;
+        let mut operations = self.tail.iter().enumerate();
+
+        operations.try_fold(init, |acc, (index, (operator, operand))| {
+            operator
+                .apply_residual(acc, operand.reduce(context)?, context)
+                .map_err(|error| self.explain(error, index, context))
+        })
+    }
+}
+
+impl<T, O> Reducible<Rational> for ExpressionWithOperator<T, O>
+where
+    T: ExpressionTrait + Reducible<Rational> + Canonical + Variables,
+    O: Operator + ExactOperator + Precedence + fmt::Display,
+{
+    fn reduce(&self, context: &Context) -> Result<Rational> {
+        let init = self.head.reduce(context)?;
+        let mut operations = self.tail.iter().enumerate();
+
+        operations.try_fold(init, |acc, (index, (operator, operand))| {
+            operator
+                .apply_exact(&acc, &operand.reduce(context)?)
+                .map_err(|error| self.explain(error, index, context))
+        })
+    }
+}
 
 #[cfg(test)]
 mod tests {
-    use std::collections::HashMap;
-
     use super::{ExpressionOperator::*, TermOperator::*, *};
     use crate::expression::arithmetic::factor::Factor::*;
-    use crate::expression::common::{
-        Expression as ExpressionTrait, ExpressionWithOperator, Reducible,
-    };
+    use crate::expression::number::FloatPolicy;
+    use crate::expression::typing::{Type, TypeCheck, Types};
+
+    #[test]
+    fn test_residual_reducibility() {
+        let mut context = Context::default();
+        context.variables.insert("D".into(), 2.into());
+
+        for &(source, expected) in &[
+            ("D + (D * E / 10)", "2 + 0.2 * E"),
+            ("E * (F - D) - 1 + D", "E * (F - 2) + 1"),
+            ("let x = D * 3 in x / E", "6 / E"),
+            ("if(D > 1, E, F) * 2", "2 * E"),
+            ("if(A, E, D) + 1", "if(A, E, 2) + 1"),
+            ("D * 4 - 8", "0"),
+        ] {
+            let expression = Expression::parse(source).unwrap().1;
+            let residual: Residual = expression.reduce(&context).unwrap();
+
+            assert_eq!(expected, residual.to_string());
+        }
+    }
+
+    #[test]
+    fn test_canonical_form() {
+        for &(source, expected) in &[
+            ("((D * 2)) + (E)", "D * 2 + E"),
+            ("D-(E-F)", "D - (E - F)"),
+            ("(D - E) - F", "D - E - F"),
+            ("D / (E × F)", "D / (E * F)"),
+            ("(D + E) * if(A,(1),2)", "(D + E) * if(A, 1, 2)"),
+            ("(let x = 1 in x) * 2", "(let x = 1 in x) * 2"),
+            ("2 * (let x = 1 in x + 1)", "2 * let x = 1 in x + 1"),
+            ("(2 * (let x = 1 in x)) + 1", "2 * (let x = 1 in x) + 1"),
+        ] {
+            let expression = Expression::parse(source).unwrap().1;
+
+            assert_eq!(expected, expression.to_string());
+        }
+    }
+
+    #[test]
+    fn test_optimization() {
+        use crate::expression::optimize::Optimize;
+
+        let context = Context::default();
+
+        for &(source, expected) in &[
+            ("D * 1 + 0", "D"),
+            ("0 + 1 * D / 1 - 0", "D"),
+            ("2 * 3 * D + (4 - 1) * E", "6 * D + 3 * E"),
+            ("D * 1.0 + (10 / 4)", "D * 1.0 + 2.5"),
+            ("if(1 > 2, D, E) + (D)", "E + D"),
+            ("D / 0 + 1 / 0", "D / 0 + 1 / 0"),
+        ] {
+            let expression = Expression::parse(source).unwrap().1;
+
+            assert_eq!(expected, expression.optimize(&context).to_string());
+        }
+    }
 
     #[test]
     fn test_expression_reducibility() {
         let expression_str = "42 + 4 * (5 / 2 - 1) - 10 * 0.625 + 1 / 2";
         let expression = Expression::parse(expression_str).unwrap().1;
-        let actual = expression.reduce(&HashMap::new()).unwrap();
+        let actual = expression.reduce(&Context::default()).unwrap();
 
-        assert_eq!(42.25, actual);
+        assert_eq!(Value::from(42.25), actual);
     }
 
     #[test]
     fn test_expression_reducibility_variables() {
         let expression_str = "42 + 4 * (5 / UNKNOWN - 1) - 10 * VALUE + 1 / 2";
         let expression = Expression::parse(expression_str).unwrap().1;
-        let mut variables = HashMap::new();
-        variables.insert("UNKNOWN".into(), 0.8);
-        variables.insert("VALUE".into(), -0.4);
+        let mut context = Context::default();
+        context.variables.insert("UNKNOWN".into(), 0.8.into());
+        context.variables.insert("VALUE".into(), (-0.4).into());
 
-        let actual = expression.reduce(&variables).unwrap();
+        let actual = expression.reduce(&context).unwrap();
 
-        assert_eq!(67.5, actual);
+        assert_eq!(Value::from(67.5), actual);
     }
 
     #[test]
     fn test_expression_undefined_vars() {
         let expression_str = "-5 + UNKNOWN";
         let expression = Expression::parse(expression_str).unwrap().1;
-        let variables = HashMap::new();
+        let context = Context::default();
 
-        let actual = expression.reduce(&variables).unwrap_err();
+        let actual =
+            Reducible::<Value>::reduce(&expression, &context).unwrap_err();
 
         assert_eq!("Variable UNKNOWN is undefined", format!("{}", actual));
     }
@@ -60,30 +286,134 @@ mod tests {
     fn test_expression_divide_by_zero() {
         let expression_str = "-5 / 0";
         let expression = Expression::parse(expression_str).unwrap().1;
-        let variables = HashMap::new();
+        let mut context = Context::default();
 
-        let actual = expression.reduce(&variables).unwrap();
+        let actual = expression.reduce(&context).unwrap();
 
         // Yeah, perfectly fine. Thanks IEEE 754.
-        assert_eq!(f64::NEG_INFINITY, actual);
+        assert_eq!(Value::from(f64::NEG_INFINITY), actual);
+
+        context.floats = FloatPolicy::DivisionByZero;
+        let actual =
+            Reducible::<Value>::reduce(&expression, &context).unwrap_err();
+
+        assert_eq!("Division by zero in -5 / 0", format!("{}", actual));
+    }
+
+    #[test]
+    fn test_exact_integers() {
+        let expression = Expression::parse("E * 3 / 3 - 1").unwrap().1;
+        let mut context = Context::default();
+        context
+            .variables
+            .insert("E".into(), 9_007_199_254_740_993.into());
+
+        let actual = expression.reduce(&context).unwrap();
+
+        assert_eq!(Value::from(9_007_199_254_740_992), actual);
+
+        context.variables.insert("E".into(), i64::MAX.into());
+        let actual =
+            Reducible::<Value>::reduce(&expression, &context).unwrap_err();
+
+        assert_eq!(
+            "Integer overflow in E * 3 where E = 9223372036854775807",
+            format!("{}", actual)
+        );
+    }
+
+    #[test]
+    fn test_float_policies() {
+        let expression = Expression::parse("D + D / (E - F)").unwrap().1;
+        let mut context = Context::default();
+        context.variables.insert("D".into(), 1.5.into());
+        context.variables.insert("E".into(), 2.0.into());
+        context.variables.insert("F".into(), 2.0.into());
+
+        let actual = expression.reduce(&context).unwrap();
+
+        assert_eq!(Value::from(f64::INFINITY), actual);
+
+        context.floats = FloatPolicy::DivisionByZero;
+        let actual =
+            Reducible::<Value>::reduce(&expression, &context).unwrap_err();
+
+        assert_eq!(
+            "Division by zero in D / (E - F) where D = 1.5, E = 2.0, F = 2.0",
+            format!("{}", actual)
+        );
+
+        context.floats = FloatPolicy::NonFinite;
+        context.variables.insert("D".into(), 1e308.into());
+        context.variables.insert("F".into(), 1.0.into());
+        let actual =
+            Reducible::<Value>::reduce(&expression, &context).unwrap_err();
+
+        assert_eq!(
+            "Result is not finite in D + D / (E - F) \
+             where D = 1e308, E = 2.0, F = 1.0",
+            format!("{}", actual)
+        );
+    }
+
+    #[test]
+    fn test_exact_reducibility() {
+        let expression = Expression::parse("D + (D * E / 10) - 0.1").unwrap().1;
+        let mut context = Context::default();
+        context.variables.insert("D".into(), 0.1.into());
+        context.variables.insert("E".into(), 2.into());
+
+        let exact: Rational = expression.reduce(&context).unwrap();
+        let float: Value = expression.reduce(&context).unwrap();
+
+        assert_eq!("1/50", exact.to_string());
+        assert_ne!(Value::from(0.02), float);
+    }
+
+    #[test]
+    fn test_operator_aliases() {
+        let expression = Expression::parse("6 × 2 ÷ 4").unwrap().1;
+
+        assert_eq!(
+            Value::from(3.0),
+            expression.reduce(&Context::default()).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_type_checking() {
+        let mut types = Types::new();
+        types.insert("A".into(), Type::Bool);
+        types.insert("D".into(), Type::Number);
+
+        let source = "D * 2 + A";
+        let expression = Expression::parse(source).unwrap().1;
+        let error = expression.type_check(&types).unwrap_err();
+
+        assert_eq!("expected number, found bool", error.message);
+        assert_eq!(8..9, error.span.resolve(source));
+
+        let expression = Expression::parse("A").unwrap().1;
+
+        assert_eq!(Ok(Some(Type::Bool)), expression.type_check(&types));
     }
 
     #[test]
     fn test_expression_parsing() {
         let expected = ExpressionWithOperator {
             head: ExpressionWithOperator {
-                head: Constant(42.0),
+                head: Constant(42.into()),
                 tail: vec![],
             },
             tail: vec![(
                 Add,
                 ExpressionWithOperator {
-                    head: Constant(4.0),
+                    head: Constant(4.into()),
                     tail: vec![(
                         Multiply,
                         ExpressionInParens(Box::new(ExpressionWithOperator {
                             head: ExpressionWithOperator {
-                                head: Constant(5.0),
+                                head: Constant(5.into()),
                                 tail: vec![(Divide, Variable("Foo".into()))],
                             },
                             tail: vec![(
@@ -106,8 +436,8 @@ mod tests {
     #[test]
     fn test_term_parsing_multiplication() {
         let expected = Term {
-            tail: vec![(Multiply, Factor::Constant(4.0))],
-            head: Factor::Constant(42.0),
+            tail: vec![(Multiply, Factor::Constant(4.into()))],
+            head: Factor::Constant(42.into()),
         };
 
         assert_eq!(Ok(("", expected)), Term::parse("42 * 4"));
@@ -117,7 +447,7 @@ mod tests {
     fn test_term_parsing_constant() {
         let expected = Term {
             tail: vec![],
-            head: Factor::Constant(42.0),
+            head: Factor::Constant(42.into()),
         };
 
         assert_eq!(Ok(("", expected)), Term::parse("42"));
@@ -128,9 +458,9 @@ mod tests {
         let expected = Term {
             tail: vec![
                 (Multiply, Factor::Variable("Foobar".into())),
-                (Divide, Factor::Constant(55.0)),
+                (Divide, Factor::Constant(55.into())),
             ],
-            head: Factor::Constant(42.0),
+            head: Factor::Constant(42.into()),
         };
 
         assert_eq!(Ok(("", expected)), Term::parse("42 * Foobar / 55"));
@@ -142,7 +472,7 @@ mod tests {
 
         let expected = Term {
             tail,
-            head: Factor::Constant(42.0),
+            head: Factor::Constant(42.into()),
         };
 
         let remainder = "+ $#&#7#R!";
@@ -1,41 +1,472 @@
 /// This module is used to parse variables in arithmetic expressions,
-/// including constants and nested parenthezised expressions.
-use std::collections::HashMap;
+/// including constants, string literals, nested parenthezised expressions,
+/// conditionals and let bindings.
+use std::fmt;
 
-use anyhow::{Context, Result};
+use anyhow::Result;
 
 use nom::branch::alt;
-use nom::bytes::complete::tag;
-use nom::character::complete::{alpha1, space0};
-use nom::combinator::map;
-use nom::number::complete as number;
-use nom::sequence::delimited;
+use nom::bytes::complete::{tag, take_while};
+use nom::character::complete::{char, space0, space1};
+use nom::combinator::{map, map_res};
+use nom::sequence::{delimited, pair, preceded, tuple};
 use nom::IResult;
+use serde_json::Value as Json;
 
 use super::expression::Expression as ArithmeticExpression;
-use crate::expression::common::{Expression, Reducible};
+use super::expression::{ExpressionOperator, Term, TermOperator};
+use super::literal::{self, Literal};
+use super::residual::Residual;
+use crate::expression::boolean::Expression as BooleanExpression;
+use crate::expression::common::{
+    spanned, token, Canonical, Context, Expression, Identifier, Nested,
+    Parenthesized, Position, Reducible, Span, Variables,
+};
+use crate::expression::json::{self, FromNode, Node, ToNode};
+use crate::expression::number::Number;
+use crate::expression::optimize::{Operand, Optimize};
+use crate::expression::rational::Rational;
+use crate::expression::truth::{PartiallyReducible, Truth};
+use crate::expression::typing::{
+    expect, unify, Type, TypeCheck, TypeError, Types,
+};
+use crate::expression::value::Value;
 
 /// An enumeration representing either a variable or a constant number.
-#[derive(PartialEq, Debug)]
+#[derive(Clone, PartialEq, Debug)]
 pub enum Factor {
-    Variable(String),
-    Constant(f64),
+    Variable(Identifier),
+    /// A number, such as `12.5%`, printed as written.
+    Constant(Literal),
+    /// A double-quoted string literal, such as `"DE"`.
+    Text(String, Span),
     ExpressionInParens(Box<ArithmeticExpression>),
+    /// `if(condition, then, otherwise)`. Only the taken branch is reduced.
+    Conditional(
+        Box<BooleanExpression>,
+        Box<ArithmeticExpression>,
+        Box<ArithmeticExpression>,
+    ),
+    /// `let x = E - F in D * x`. The bound expression is reduced once, and
+    /// the name is only visible in the body, where it shadows variables.
+    Binding(
+        Identifier,
+        Box<ArithmeticExpression>,
+        Box<ArithmeticExpression>,
+    ),
 }
 
-impl Reducible<f64> for Factor {
-    fn reduce(&self, variables_table: &HashMap<String, f64>) -> Result<f64> {
+impl Reducible<Value> for Factor {
+    fn reduce(&self, context: &Context) -> Result<Value> {
         match self {
-            Self::Constant(constant) => Ok(*constant),
-            Self::Variable(string) => variables_table
-                .get(string)
-                .map(|&val| val)
-                .with_context(|| format!("Variable {} is undefined", string)),
+            Self::Constant(constant) => {
+                constant.convert(context.arithmetic).map(Value::Number)
+            },
+            Self::Text(text, _) => Ok(Value::String(text.clone())),
+            Self::Variable(identifier) => {
+                match context.get(&identifier.name)? {
+                    Value::Number(number) => {
+                        context.arithmetic.convert(*number).map(Value::Number)
+                    },
+                    value => Ok(value.clone()),
+                }
+            },
+            Self::ExpressionInParens(expression) => expression.reduce(context),
+            Self::Conditional(condition, then, otherwise) => {
+                if condition.reduce(context)? {
+                    then.reduce(context)
+                } else {
+                    otherwise.reduce(context)
+                }
+            },
+            Self::Binding(identifier, bound, body) => {
+                let value: Value = bound.reduce(context)?;
+                let mut scope = context.clone();
+                scope.variables.insert(identifier.name.clone(), value);
+
+                body.reduce(&scope)
+            },
+        }
+    }
+}
+
+/// Evaluates the factor without any rounding. Conditions of conditionals
+/// are still evaluated the usual way.
+impl Reducible<Rational> for Factor {
+    fn reduce(&self, context: &Context) -> Result<Rational> {
+        match self {
+            Self::Constant(constant) => Rational::from_number(constant.value),
+            Self::Text(..) => anyhow::bail!("Expected number, found string"),
+            Self::Variable(identifier) => {
+                match context.exact_bindings.get(&identifier.name) {
+                    Some(value) => Ok(value.clone()),
+                    None => Rational::from_number(
+                        context.get(&identifier.name)?.as_number()?,
+                    ),
+                }
+            },
+            Self::ExpressionInParens(expression) => expression.reduce(context),
+            Self::Conditional(condition, then, otherwise) => {
+                if condition.reduce(context)? {
+                    then.reduce(context)
+                } else {
+                    otherwise.reduce(context)
+                }
+            },
+            Self::Binding(identifier, bound, body) => {
+                let value: Rational = bound.reduce(context)?;
+                let mut scope = context.clone();
+                scope.bind_exact(&identifier.name, value);
+
+                body.reduce(&scope)
+            },
+        }
+    }
+}
+
+/// Evaluates the factor with some variables missing. Conditionals whose
+/// condition depends on the missing variables, as well as bindings of
+/// formulas, are left as they are, with the known parts folded.
+impl Reducible<Residual> for Factor {
+    fn reduce(&self, context: &Context) -> Result<Residual> {
+        match self {
+            Self::Variable(identifier)
+                if context.get(&identifier.name).is_err() =>
+            {
+                Ok(Residual::symbol(identifier.name.clone()))
+            },
+            Self::Constant(_) | Self::Text(..) | Self::Variable(_) => {
+                self.reduce(context).map(Residual::Known)
+            },
+            Self::ExpressionInParens(expression) => expression.reduce(context),
+            Self::Conditional(condition, then, otherwise) => {
+                match condition.reduce_partially(context)? {
+                    Truth::True => then.reduce(context),
+                    Truth::False => otherwise.reduce(context),
+                    Truth::Unknown => {
+                        let then: Residual = then.reduce(context)?;
+                        let otherwise: Residual = otherwise.reduce(context)?;
+
+                        Ok(Residual::symbol(format!(
+                            "if({}, {}, {})",
+                            condition, then, otherwise
+                        )))
+                    },
+                }
+            },
+            Self::Binding(identifier, bound, body) => {
+                let name = &identifier.name;
+                let mut scope = context.clone();
+
+                match bound.reduce(context)? {
+                    Residual::Known(value) => {
+                        scope.variables.insert(name.clone(), value);
+
+                        body.reduce(&scope)
+                    },
+                    bound => {
+                        scope.variables.remove(name);
+                        scope.constants.remove(name);
+
+                        match body.reduce(&scope)? {
+                            Residual::Known(value) => {
+                                Ok(Residual::Known(value))
+                            },
+                            body => Ok(Residual::symbol(format!(
+                                "(let {} = {} in {})",
+                                name, bound, body
+                            ))),
+                        }
+                    },
+                }
+            },
+        }
+    }
+}
+
+impl Operand for Factor {
+    fn constant(&self) -> Option<Value> {
+        match self {
+            Self::Constant(constant) => Some(Value::Number(constant.value)),
+            Self::Text(text, _) => Some(Value::String(text.clone())),
+            _ => None,
+        }
+    }
+
+    fn from_constant(value: Value) -> Option<Self> {
+        match value {
+            Value::Number(number) => Some(Self::Constant(number.into())),
+            Value::String(text) => Some(Self::Text(text, Span::default())),
+            Value::Bool(_) => None,
+        }
+    }
+}
+
+impl Optimize for Factor {
+    fn optimize(self, context: &Context) -> Self {
+        match self {
+            Self::ExpressionInParens(expression) => {
+                let expression = expression.optimize(context);
+
+                // Parentheses around a lone factor are redundant.
+                if expression.tail.is_empty() && expression.head.tail.is_empty()
+                {
+                    expression.head.head
+                } else {
+                    Self::ExpressionInParens(Box::new(expression))
+                }
+            },
+            Self::Conditional(condition, then, otherwise) => {
+                let condition = condition.optimize(context);
+
+                match condition.constant() {
+                    Some(Value::Bool(true)) => {
+                        Self::ExpressionInParens(then).optimize(context)
+                    },
+                    Some(Value::Bool(false)) => {
+                        Self::ExpressionInParens(otherwise).optimize(context)
+                    },
+                    _ => Self::Conditional(
+                        Box::new(condition),
+                        Box::new(then.optimize(context)),
+                        Box::new(otherwise.optimize(context)),
+                    ),
+                }
+            },
+            Self::Binding(identifier, bound, body) => Self::Binding(
+                identifier,
+                Box::new(bound.optimize(context)),
+                Box::new(body.optimize(context)),
+            ),
+            // Literals are converted up front, so that constants are folded
+            // from their spelling rather than from the float.
+            Self::Constant(constant) => Self::Constant(
+                constant.converted(context.arithmetic).unwrap_or(constant),
+            ),
+            factor => factor,
+        }
+    }
+}
+
+impl Variables for Factor {
+    fn variables<'a>(&'a self, names: &mut Vec<&'a str>) {
+        match self {
+            Self::Variable(identifier) => names.push(&identifier.name),
+            Self::Constant(_) | Self::Text(..) => {},
+            Self::ExpressionInParens(expression) => expression.variables(names),
+            Self::Conditional(condition, then, otherwise) => {
+                condition.variables(names);
+                then.variables(names);
+                otherwise.variables(names);
+            },
+            Self::Binding(_, bound, body) => {
+                bound.variables(names);
+                body.variables(names);
+            },
+        }
+    }
+}
+
+impl Canonical for Factor {
+    /// Parentheses are left to the position, so that redundant ones, such
+    /// as in `(D * 2) + 1`, are not printed.
+    fn precedence(&self) -> Option<u8> {
+        match self {
+            Self::ExpressionInParens(expression) => expression.precedence(),
+            _ => None,
+        }
+    }
+
+    fn write(
+        &self,
+        formatter: &mut fmt::Formatter,
+        position: Position,
+    ) -> fmt::Result {
+        match self {
+            Self::Variable(identifier) => write!(formatter, "{}", identifier),
+            Self::Constant(constant) => write!(formatter, "{}", constant),
+            Self::Text(text, _) => write!(formatter, "\"{}\"", text),
+            Self::ExpressionInParens(expression) => {
+                expression.write(formatter, position)
+            },
+            Self::Conditional(condition, then, otherwise) => {
+                write!(formatter, "if({}, {}, {})", condition, then, otherwise)
+            },
+            // The body is greedy, so it would swallow whatever follows.
+            Self::Binding(identifier, bound, body) if position.followed => {
+                write!(
+                    formatter,
+                    "(let {} = {} in {})",
+                    identifier, bound, body
+                )
+            },
+            Self::Binding(identifier, bound, body) => {
+                write!(formatter, "let {} = {} in {}", identifier, bound, body)
+            },
+        }
+    }
+}
+
+impl fmt::Display for Factor {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        self.write(formatter, Position::default())
+    }
+}
+
+impl Parenthesized for Factor {
+    type Root = ArithmeticExpression;
+
+    fn parenthesized(&self) -> Option<&Self::Root> {
+        match self {
+            Self::ExpressionInParens(expression) => Some(expression),
+            _ => None,
+        }
+    }
+
+    fn into_parenthesized(self) -> Result<Self::Root, Self> {
+        match self {
+            Self::ExpressionInParens(expression) => Ok(*expression),
+            factor => Err(factor),
+        }
+    }
+}
+
+impl Nested<ArithmeticExpression> for ArithmeticExpression {
+    fn from_root(root: ArithmeticExpression) -> Self {
+        root
+    }
+}
+
+impl Nested<ArithmeticExpression> for Term {
+    fn from_root(root: ArithmeticExpression) -> Self {
+        root.into_operand().unwrap_or_else(|root| {
+            Factor::ExpressionInParens(Box::new(root)).into()
+        })
+    }
+}
+
+impl Nested<ArithmeticExpression> for Factor {
+    fn from_root(root: ArithmeticExpression) -> Self {
+        let root = match root.into_operand().map(Term::into_operand) {
+            Ok(Ok(factor)) => return factor,
+            Ok(Err(term)) => term.into(),
+            Err(root) => root,
+        };
+
+        Self::ExpressionInParens(Box::new(root))
+    }
+}
+
+impl ToNode for Factor {
+    fn to_node(&self) -> Node {
+        match self {
+            Self::Variable(identifier) => Node::Variable {
+                name: identifier.name.clone(),
+            },
+            Self::Constant(constant) => Node::number(constant.value),
+            Self::Text(text, _) => Node::Literal {
+                value: Json::String(text.clone()),
+            },
+            Self::ExpressionInParens(expression) => expression.to_node(),
+            Self::Conditional(condition, then, otherwise) => Node::If {
+                condition: Box::new(condition.to_node()),
+                then: Box::new(then.to_node()),
+                otherwise: Box::new(otherwise.to_node()),
+            },
+            Self::Binding(identifier, bound, body) => Node::Let {
+                name: identifier.name.clone(),
+                value: Box::new(bound.to_node()),
+                body: Box::new(body.to_node()),
+            },
+        }
+    }
+}
+
+impl FromNode for Factor {
+    fn from_node(node: &Node) -> Result<Self> {
+        let factor = match node {
+            Node::Literal {
+                value: Json::String(text),
+            } => {
+                // String literals have no escapes.
+                if text.contains('"') {
+                    anyhow::bail!("Strings can't contain quotes: {}", text);
+                }
+
+                Self::Text(text.clone(), Span::default())
+            },
+            Node::Literal { .. } => {
+                Self::Constant(Number::from_node(node)?.into())
+            },
+            Node::Variable { name } => Self::Variable(json::name(name)?),
+            Node::If {
+                condition,
+                then,
+                otherwise,
+            } => Self::Conditional(
+                Box::new(BooleanExpression::from_node(condition)?),
+                Box::new(ArithmeticExpression::from_node(then)?),
+                Box::new(ArithmeticExpression::from_node(otherwise)?),
+            ),
+            Node::Let { name, value, body } => Self::Binding(
+                json::name(name)?,
+                Box::new(ArithmeticExpression::from_node(value)?),
+                Box::new(ArithmeticExpression::from_node(body)?),
+            ),
+            _ if node.is_operation::<TermOperator>()
+                || node.is_operation::<ExpressionOperator>() =>
+            {
+                let expression = ArithmeticExpression::from_node(node)?;
+
+                Self::ExpressionInParens(Box::new(expression))
+            },
+            _ => return Err(node.unexpected("a formula")),
+        };
+
+        Ok(factor)
+    }
+}
+
+impl TypeCheck for Factor {
+    fn type_check(&self, types: &Types) -> Result<Option<Type>, TypeError> {
+        match self {
+            Self::Constant(_) => Ok(Some(Type::Number)),
+            Self::Text(..) => Ok(Some(Type::String)),
+            Self::Variable(identifier) => {
+                Ok(types.get(&identifier.name).copied())
+            },
             Self::ExpressionInParens(expression) => {
-                expression.reduce(variables_table)
+                expression.type_check(types)
+            },
+            Self::Conditional(condition, then, otherwise) => {
+                expect(condition.as_ref(), Type::Bool, types)?;
+
+                unify(then.as_ref(), otherwise.as_ref(), types)
+            },
+            Self::Binding(identifier, bound, body) => {
+                let mut scope = types.clone();
+                match bound.type_check(types)? {
+                    Some(bound) => scope.insert(identifier.name.clone(), bound),
+                    None => scope.remove(&identifier.name),
+                };
+
+                body.type_check(&scope)
             },
         }
     }
+
+    fn span(&self) -> Option<Span> {
+        match self {
+            Self::Constant(_) => None,
+            Self::Text(_, span) => Some(*span),
+            Self::Variable(identifier) => Some(identifier.span),
+            Self::ExpressionInParens(expression) => expression.span(),
+            Self::Conditional(_, then, otherwise) => {
+                then.span().or_else(|| otherwise.span())
+            },
+            Self::Binding(_, _, body) => body.span(),
+        }
+    }
 }
 
 impl Expression for Factor {
@@ -44,14 +475,18 @@ impl Expression for Factor {
     ///
     /// # Example
     ///
-    /// ```
+    /// ```ignore
     /// use crate::expression::arithmetic::factor::Factor;
     ///
     /// let variable = Factor::parse(" 42.0000 ").unwrap().1;
-    /// assert_eq!(variable, Factor::Constant(42.0));
+    /// assert_eq!(variable, Factor::Constant(42.0.into()));
     /// ```
     fn parse(input: &str) -> IResult<&str, Self> {
-        delimited(space0, alt((expr, constant, variable)), space0)(input)
+        delimited(
+            space0,
+            alt((expr, conditional, binding, constant, text, variable)),
+            space0,
+        )(input)
     }
 }
 
@@ -63,12 +498,60 @@ fn expr(input: &str) -> IResult<&str, Factor> {
     })(input)
 }
 
+fn conditional(input: &str) -> IResult<&str, Factor> {
+    let arguments = tuple((
+        BooleanExpression::parse,
+        preceded(tag(","), ArithmeticExpression::parse),
+        preceded(tag(","), ArithmeticExpression::parse),
+    ));
+    let parser = preceded(
+        pair(tag("if"), space0),
+        delimited(tag("("), arguments, tag(")")),
+    );
+
+    map(parser, |(condition, then, otherwise)| {
+        Factor::Conditional(
+            Box::new(condition),
+            Box::new(then),
+            Box::new(otherwise),
+        )
+    })(input)
+}
+
+/// Parses a let binding. The body extends as far to the right as possible,
+/// so `let x = 2 in x * 3 + 1` is `7`.
+fn binding(input: &str) -> IResult<&str, Factor> {
+    let name = delimited(pair(token("let"), space1), Identifier::parse, space0);
+    let parser = tuple((
+        name,
+        preceded(tag("="), ArithmeticExpression::parse),
+        preceded(token("in"), ArithmeticExpression::parse),
+    ));
+
+    map(parser, |(identifier, bound, body)| {
+        Factor::Binding(identifier, Box::new(bound), Box::new(body))
+    })(input)
+}
+
+/// Parses a number, such as `1_000`, `0x1F`, `1e-3` or `12.5%`. Integers
+/// which don't fit into `i64` are rejected rather than rounded.
 fn constant(input: &str) -> IResult<&str, Factor> {
-    map(number::double, |constant| Factor::Constant(constant))(input)
+    map_res(literal::recognize, |literal| {
+        literal::parse(literal).map(Factor::Constant)
+    })(input)
+}
+
+fn text(input: &str) -> IResult<&str, Factor> {
+    let contents = take_while(|character| character != '"');
+    let literal = delimited(char('"'), contents, char('"'));
+
+    map(spanned(literal), |(text, span)| {
+        Factor::Text(text.into(), span)
+    })(input)
 }
 
 fn variable(input: &str) -> IResult<&str, Factor> {
-    map(alpha1, |name: &str| Factor::Variable(name.into()))(input)
+    map(Identifier::parse, Factor::Variable)(input)
 }
 
 #[cfg(test)]
@@ -83,7 +566,27 @@ mod tests {
 
     #[test]
     fn test_parsing_constant() {
-        assert_eq!(Ok(("", Factor::Constant(42.0))), Factor::parse("42"));
+        assert_eq!(Ok(("", Factor::Constant(42.into()))), Factor::parse("42"));
+        assert!(matches!(
+            Factor::parse("42").unwrap().1,
+            Factor::Constant(Literal {
+                value: Number::Int(42),
+                ..
+            })
+        ));
+        assert!(matches!(
+            Factor::parse("4.2e1").unwrap().1,
+            Factor::Constant(Literal {
+                value: Number::Float(_),
+                ..
+            })
+        ));
+        assert!(Factor::parse("99999999999999999999").is_err());
+        assert!(Factor::parse("1__000").is_err());
+        assert_eq!(
+            Ok(("", Factor::Constant(0.125.into()))),
+            Factor::parse("12.5%")
+        );
     }
 
     #[test]
@@ -94,6 +597,14 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_parsing_path() {
+        assert_eq!(
+            Ok(("", Factor::Variable("customer.rate_2024".into()))),
+            Factor::parse("customer.rate_2024")
+        );
+    }
+
     #[test]
     fn test_invalid_input() {
         use nom::error::ErrorKind;
@@ -109,7 +620,7 @@ mod tests {
     fn test_parsing_parenthized_expression() {
         let expected = ExpressionInParens(Box::new(Expression {
             head: Term {
-                head: Factor::Constant(5.0),
+                head: Factor::Constant(5.into()),
                 tail: vec![(Multiply, Factor::Variable("VARNAME".into()))],
             },
             tail: vec![],
@@ -117,4 +628,100 @@ mod tests {
 
         assert_eq!(expected, Factor::parse("(5 * VARNAME)").unwrap().1,);
     }
+
+    #[test]
+    fn test_conditional_reducibility() {
+        let factor = Factor::parse("if(FLAG && D > 1, D * 2, D / 0)").unwrap();
+        let mut context = Context::default();
+        context.variables.insert("FLAG".into(), true.into());
+        context.variables.insert("D".into(), 1.5.into());
+
+        assert_eq!("", factor.0);
+        assert_eq!(Value::from(3.0), factor.1.reduce(&context).unwrap());
+
+        context.variables.insert("FLAG".into(), false.into());
+
+        assert_eq!(
+            Value::from(f64::INFINITY),
+            factor.1.reduce(&context).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_conditional_evaluates_taken_branch_only() {
+        let factor = Factor::parse("if (true, 1, UNKNOWN)").unwrap().1;
+
+        assert_eq!(Value::from(1), factor.reduce(&Context::default()).unwrap());
+    }
+
+    #[test]
+    fn test_bindings() {
+        let factor =
+            Factor::parse("let x = E - F in D + D * x / 25.5").unwrap();
+        let mut context = Context::default();
+        context.variables.insert("D".into(), 51.into());
+        context.variables.insert("E".into(), 4.into());
+        context.variables.insert("F".into(), 2.into());
+        // Bindings shadow variables, but only within their bodies.
+        context.variables.insert("x".into(), 100.into());
+
+        assert_eq!("", factor.0);
+        assert_eq!(Value::from(55.0), factor.1.reduce(&context).unwrap());
+
+        let exact: Rational = factor.1.reduce(&context).unwrap();
+        assert_eq!(Rational::from(55), exact);
+
+        // Conditions see the bindings of the exact evaluation as well.
+        let factor = Factor::parse("let x = 1 in if(x > 0, x, 0)").unwrap().1;
+        let context = Context::default();
+
+        assert_eq!(Value::from(1), factor.reduce(&context).unwrap());
+        let exact: Rational = factor.reduce(&context).unwrap();
+        assert_eq!(Rational::from(1), exact);
+
+        let factor = Factor::parse("(let x = 2 in x * 3) + x").unwrap().1;
+        let expected = ExpressionInParens(Box::new(
+            Expression::parse("let x = 2 in x * 3").unwrap().1,
+        ));
+
+        assert_eq!(expected, factor);
+        assert!(Factor::parse("let in = 2 in 3").is_err());
+    }
+
+    #[test]
+    fn test_parsing_text() {
+        assert_eq!(
+            Ok(("", Factor::Text("DE".into(), Span::default()))),
+            Factor::parse(r#" "DE" "#)
+        );
+        assert_eq!(
+            Ok(("", Factor::Text("".into(), Span::default()))),
+            Factor::parse(r#""""#)
+        );
+    }
+
+    #[test]
+    fn test_type_checking() {
+        let mut types = Types::new();
+        types.insert("A".into(), Type::Bool);
+        types.insert("D".into(), Type::Number);
+
+        let source = r#"if(A, D, "none")"#;
+        let factor = Factor::parse(source).unwrap().1;
+        let error = factor.type_check(&types).unwrap_err();
+
+        assert_eq!("mismatched types number and string", error.message);
+        assert_eq!(9..15, error.span.resolve(source));
+
+        let factor = Factor::parse("if(A, D, UNDECLARED)").unwrap().1;
+
+        assert_eq!(Ok(Some(Type::Number)), factor.type_check(&types));
+
+        let source = "let x = A in x * 2";
+        let factor = Factor::parse(source).unwrap().1;
+        let error = factor.type_check(&types).unwrap_err();
+
+        assert_eq!("expected number, found bool", error.message);
+        assert_eq!(13..14, error.span.resolve(source));
+    }
 }
@@ -0,0 +1,373 @@
+/// This module reads numeric literals, such as `1_000_000`, `0x1F`,
+/// `1e-3` or `12.5%`.
+use std::fmt;
+
+use anyhow::{Context as _, Result};
+use nom::error::ErrorKind;
+use nom::IResult;
+
+use crate::expression::decimal::Decimal;
+use crate::expression::number::{Arithmetic, Number};
+
+/// A number, along with its spelling if it was written down, so that
+/// `12.5%` is printed back as `12.5%` rather than `0.125`.
+///
+/// Spellings never take part in comparisons, so that `0x1F` equals `31`.
+#[derive(Clone, Debug)]
+pub struct Literal {
+    pub value: Number,
+    spelling: Option<String>,
+}
+
+impl PartialEq for Literal {
+    fn eq(&self, other: &Self) -> bool {
+        self.value == other.value
+    }
+}
+
+impl fmt::Display for Literal {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        match &self.spelling {
+            Some(spelling) => formatter.write_str(spelling),
+            None => write!(formatter, "{}", self.value),
+        }
+    }
+}
+
+impl Literal {
+    /// Converts the number into the numbers of the mode. Decimals are read
+    /// from the spelling rather than from the float, so that long literals
+    /// such as `0.123456789012345678` keep all of their digits.
+    pub fn convert(&self, arithmetic: Arithmetic) -> Result<Number> {
+        if let (
+            Arithmetic::Decimal { scale, rounding },
+            Some(spelling),
+            Number::Float(_),
+        ) = (arithmetic, &self.spelling, self.value)
+        {
+            if let Reading::Scientific(text) = read(spelling)? {
+                return Decimal::parse(&text, scale, rounding)
+                    .map(Number::Decimal);
+            }
+        }
+
+        arithmetic.convert(self.value)
+    }
+
+    /// Converts the number as `convert` does, keeping the spelling.
+    pub fn converted(&self, arithmetic: Arithmetic) -> Result<Self> {
+        Ok(Self {
+            value: self.convert(arithmetic)?,
+            spelling: self.spelling.clone(),
+        })
+    }
+}
+
+impl<T: Into<Number>> From<T> for Literal {
+    fn from(value: T) -> Self {
+        Self {
+            value: value.into(),
+            spelling: None,
+        }
+    }
+}
+
+/// Recognizes anything which looks like a number, however malformed, so
+/// that `1__000` is rejected as a whole rather than read as `1`.
+pub fn recognize(input: &str) -> IResult<&str, &str> {
+    let unsigned = input.strip_prefix(|c| c == '+' || c == '-');
+    let unsigned = unsigned.unwrap_or(input);
+    let sign = input.len() - unsigned.len();
+
+    // Numbers start with a digit, or a point followed by one.
+    let digits = unsigned.strip_prefix('.').unwrap_or(unsigned);
+    if !digits.starts_with(|c: char| c.is_ascii_digit()) {
+        return Err(nom::Err::Error((input, ErrorKind::Digit)));
+    }
+
+    let hexadecimal = unsigned.starts_with("0x") || unsigned.starts_with("0X");
+    let mut length = unsigned.len();
+    let mut previous = ' ';
+
+    for (index, character) in unsigned.char_indices() {
+        let exponent_sign = !hexadecimal
+            && (character == '+' || character == '-')
+            && (previous == 'e' || previous == 'E');
+
+        if character == '%' {
+            length = index + 1;
+            break;
+        }
+
+        if !(character.is_ascii_alphanumeric()
+            || character == '_'
+            || character == '.'
+            || exponent_sign)
+        {
+            length = index;
+            break;
+        }
+
+        previous = character;
+    }
+
+    Ok((&input[sign + length..], &input[..sign + length]))
+}
+
+/// Reads a literal recognized by `recognize`, keeping its spelling.
+pub fn parse(literal: &str) -> Result<Literal> {
+    Ok(Literal {
+        value: parse_number(literal)?,
+        spelling: Some(literal.into()),
+    })
+}
+
+/// Reads the number of a literal. Numbers are integers unless they have a
+/// fractional part, an exponent or are percentages.
+fn parse_number(literal: &str) -> Result<Number> {
+    match read(literal)? {
+        Reading::Int(value) => Ok(Number::Int(value)),
+        Reading::Scientific(text) => {
+            let value: f64 = text.parse()?;
+
+            anyhow::ensure!(value.is_finite(), "does not fit into a float");
+
+            Ok(Number::Float(value))
+        },
+    }
+}
+
+/// A literal read up to the choice of the number type.
+enum Reading {
+    Int(i64),
+    /// The literal in scientific notation, without underscores or a
+    /// percent sign, such as `-12.3e-2` for `-12.3%`.
+    Scientific(String),
+}
+
+fn read(literal: &str) -> Result<Reading> {
+    let unsigned = literal.strip_prefix(|c| c == '+' || c == '-');
+    let unsigned = unsigned.unwrap_or(literal);
+    let sign = &literal[..literal.len() - unsigned.len()];
+
+    if let Some(digits) = unsigned
+        .strip_prefix("0x")
+        .or_else(|| unsigned.strip_prefix("0X"))
+    {
+        let digits = without_underscores(digits, |c| c.is_ascii_hexdigit())?;
+
+        anyhow::ensure!(!digits.is_empty(), "missing hexadecimal digits");
+        if let Some(character) = digits.chars().find(|c| !c.is_ascii_hexdigit())
+        {
+            anyhow::bail!("invalid hexadecimal digit {}", character);
+        }
+
+        let value = i64::from_str_radix(&format!("{}{}", sign, digits), 16);
+
+        return value
+            .ok()
+            .map(Reading::Int)
+            .context("does not fit into a 64-bit integer");
+    }
+
+    let (body, percentage) = match unsigned.strip_suffix('%') {
+        Some(body) => (body, true),
+        None => (unsigned, false),
+    };
+    let body = without_underscores(body, |c| c.is_ascii_digit())?;
+    let (mantissa, exponent) = match body.find(['e', 'E']) {
+        Some(index) => (&body[..index], Some(&body[index + 1..])),
+        None => (body.as_str(), None),
+    };
+
+    if let Some(character) =
+        mantissa.chars().find(|&c| !c.is_ascii_digit() && c != '.')
+    {
+        anyhow::bail!("unexpected character {}", character);
+    }
+    anyhow::ensure!(
+        mantissa.matches('.').count() <= 1,
+        "more than one decimal point"
+    );
+
+    let exponent: i32 = match exponent {
+        Some(exponent) => {
+            let digits = exponent.strip_prefix(|c| c == '+' || c == '-');
+
+            anyhow::ensure!(
+                digits
+                    .unwrap_or(exponent)
+                    .starts_with(|c: char| c.is_ascii_digit()),
+                "missing exponent digits"
+            );
+
+            exponent.parse().ok().context("invalid exponent")?
+        },
+        None if !mantissa.contains('.') && !percentage => {
+            let value = format!("{}{}", sign, mantissa).parse();
+
+            return value
+                .ok()
+                .map(Reading::Int)
+                .context("does not fit into a 64-bit integer");
+        },
+        None => 0,
+    };
+
+    // Percentages are shifted in text, so that `12.3%` is read as if it
+    // were written `0.123`, without rounding twice.
+    let exponent = if percentage { exponent - 2 } else { exponent };
+
+    Ok(Reading::Scientific(format!(
+        "{}{}e{}",
+        sign, mantissa, exponent
+    )))
+}
+
+/// Finds the first malformed number in the rule, to explain why it can't
+/// be parsed.
+pub fn malformed_number(input: &str) -> Option<anyhow::Error> {
+    let mut previous = ' ';
+
+    for (start, character) in input.char_indices() {
+        // Digits within identifiers, such as `rate_2024`, are not numbers.
+        let within_word =
+            previous.is_alphanumeric() || previous == '_' || previous == '.';
+        previous = character;
+
+        if within_word {
+            continue;
+        }
+
+        if let Ok((rest, literal)) = recognize(&input[start..]) {
+            if let Err(error) = parse_number(literal) {
+                let end = input.len() - rest.len();
+
+                return Some(anyhow::anyhow!(
+                    "Malformed number at {}..{} ({}): {}",
+                    start,
+                    end,
+                    literal,
+                    error
+                ));
+            }
+        }
+    }
+
+    None
+}
+
+/// Drops underscores which separate digits, such as in `1_000`.
+fn without_underscores(
+    digits: &str,
+    is_digit: fn(char) -> bool,
+) -> Result<String> {
+    let characters: Vec<char> = digits.chars().collect();
+
+    for (index, &character) in characters.iter().enumerate() {
+        let separates_digits = index > 0
+            && is_digit(characters[index - 1])
+            && characters.get(index + 1).is_some_and(|&c| is_digit(c));
+
+        anyhow::ensure!(
+            character != '_' || separates_digits,
+            "underscores are only allowed between digits"
+        );
+    }
+
+    Ok(digits.replace('_', ""))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn number(literal: &str) -> String {
+        parse_number(literal).unwrap().to_string()
+    }
+
+    fn error(literal: &str) -> String {
+        parse_number(literal).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn test_recognition() {
+        assert_eq!(Ok((" * 2", "1_000")), recognize("1_000 * 2"));
+        assert_eq!(Ok(("", "-1e-3")), recognize("-1e-3"));
+        assert_eq!(Ok((")", "12.5%")), recognize("12.5%)"));
+        assert_eq!(Ok(("-3", "0x1E")), recognize("0x1E-3"));
+        assert_eq!(Ok(("", "1__0")), recognize("1__0"));
+        assert!(recognize("rate").is_err());
+    }
+
+    #[test]
+    fn test_parsing() {
+        assert_eq!("1000000", number("1_000_000"));
+        assert_eq!("0.125", number("12.5%"));
+        assert_eq!("0.123", number("12.3%"));
+        assert_eq!("-0.5", number("-50%"));
+        assert_eq!("31", number("0x1F"));
+        assert_eq!("-255", number("-0xff"));
+        assert_eq!("0.001", number("1e-3"));
+        assert_eq!("1500.0", number("1.5E+3"));
+        assert_eq!("0.5", number(".5"));
+        assert!(matches!(parse_number("42"), Ok(Number::Int(42))));
+        assert!(matches!(
+            parse_number("-9223372036854775808"),
+            Ok(Number::Int(_))
+        ));
+    }
+
+    #[test]
+    fn test_decimal_conversion() {
+        let decimal = |literal: &str, scale| {
+            let arithmetic = Arithmetic::Decimal {
+                scale,
+                rounding: Default::default(),
+            };
+
+            parse(literal)
+                .unwrap()
+                .convert(arithmetic)
+                .unwrap()
+                .to_string()
+        };
+
+        assert_eq!("0.123456789012345678", decimal("0.123456789012345678", 18));
+        assert_eq!("0.125", decimal("12.5%", 3));
+        assert_eq!("1500.00", decimal("1.5E+3", 2));
+        assert_eq!("1000000.00", decimal("1_000_000", 2));
+        assert_eq!("31.00", decimal("0x1F", 2));
+    }
+
+    #[test]
+    fn test_errors() {
+        let underscores = "underscores are only allowed between digits";
+
+        assert_eq!(underscores, error("1__000"));
+        assert_eq!(underscores, error("1_000_"));
+        assert_eq!(underscores, error("1_.5"));
+        assert_eq!("invalid hexadecimal digit G", error("0x1G"));
+        assert_eq!("missing hexadecimal digits", error("0x"));
+        assert_eq!("missing exponent digits", error("1e"));
+        assert_eq!("more than one decimal point", error("1.2.3"));
+        assert_eq!("unexpected character x", error("3x"));
+        assert_eq!(
+            "does not fit into a 64-bit integer",
+            error("99999999999999999999")
+        );
+        assert_eq!("does not fit into a float", error("1e400"));
+    }
+
+    #[test]
+    fn test_malformed_number() {
+        let error = malformed_number("H = M => K = D * 1__000").unwrap();
+
+        assert_eq!(
+            "Malformed number at 17..23 (1__000): underscores are only \
+             allowed between digits",
+            error.to_string()
+        );
+        assert!(malformed_number("H = M2 => K[2] = rate_2024 * 1e-3").is_none());
+    }
+}
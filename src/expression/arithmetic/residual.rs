@@ -0,0 +1,303 @@
+/// This module implements residual formulas, that is whatever is left of
+/// an arithmetic expression once the known variables are substituted and
+/// the constants are folded, such as `2 + 0.2 * E`.
+use std::fmt;
+
+use anyhow::Result;
+
+use crate::expression::common::Context;
+use crate::expression::number::Number;
+use crate::expression::value::Value;
+
+/// The result of evaluating an expression with some variables missing.
+#[derive(Clone, PartialEq, Debug)]
+pub enum Residual {
+    Known(Value),
+    /// A sum of products, which depends on the missing variables.
+    Formula(Vec<Product>),
+}
+
+/// A coefficient multiplied and divided by factors, such as `0.2 * E / F`.
+/// Factors are names of missing variables or parenthesized formulas, and
+/// constants have none.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Product {
+    coefficient: Number,
+    /// Factors along with whether they divide the product.
+    factors: Vec<(bool, String)>,
+}
+
+impl Residual {
+    /// A formula consisting of a single opaque factor, such as the name of
+    /// a missing variable.
+    pub fn symbol(text: String) -> Self {
+        Self::Formula(vec![Product {
+            coefficient: Number::Int(1),
+            factors: vec![(false, text)],
+        }])
+    }
+
+    /// Adds, or subtracts, the residuals. Constants of the sum are folded
+    /// into the first one of them.
+    pub fn add(
+        self,
+        rhs: Self,
+        subtract: bool,
+        context: &Context,
+    ) -> Result<Self> {
+        if let (Self::Known(lhs), Self::Known(rhs)) = (&self, &rhs) {
+            let (lhs, rhs) = (lhs.as_number()?, rhs.as_number()?);
+            let result = if subtract {
+                lhs.checked_sub(rhs)?
+            } else {
+                lhs.checked_add(rhs)?
+            };
+
+            return context
+                .floats
+                .check(result)
+                .map(Value::Number)
+                .map(Self::Known);
+        }
+
+        let mut products = self.products()?;
+        for mut product in rhs.products()? {
+            if subtract {
+                product.coefficient = negate(product.coefficient)?;
+            }
+
+            products.push(product);
+        }
+
+        let mut constant: Option<(usize, Number)> = None;
+        let mut terms = vec![];
+        for product in products {
+            match (product.factors.is_empty(), constant) {
+                (true, None) => {
+                    constant = Some((terms.len(), product.coefficient));
+                },
+                (true, Some((index, sum))) => {
+                    let sum = sum.checked_add(product.coefficient)?;
+                    constant = Some((index, context.floats.check(sum)?));
+                },
+                (false, _) => terms.push(product),
+            }
+        }
+
+        match constant {
+            Some((_, sum)) if terms.is_empty() => {
+                Ok(Self::Known(Value::Number(sum)))
+            },
+            Some((index, sum)) if sum != Number::Int(0) => {
+                terms.insert(
+                    index,
+                    Product {
+                        coefficient: sum,
+                        factors: vec![],
+                    },
+                );
+
+                Ok(Self::Formula(terms))
+            },
+            _ => Ok(Self::Formula(terms)),
+        }
+    }
+
+    /// Multiplies, or divides, the residuals. Known numbers are merged into
+    /// coefficients, distributing them over sums.
+    pub fn multiply(
+        self,
+        rhs: Self,
+        divide: bool,
+        context: &Context,
+    ) -> Result<Self> {
+        let apply = |lhs: Number, rhs: Number| -> Result<Number> {
+            let result = if divide {
+                lhs.checked_div(context.floats.check_divisor(rhs)?)?
+            } else {
+                lhs.checked_mul(rhs)?
+            };
+
+            context.floats.check(result)
+        };
+
+        match (self, rhs) {
+            (Self::Known(lhs), Self::Known(rhs)) => {
+                let result = apply(lhs.as_number()?, rhs.as_number()?)?;
+
+                Ok(Self::Known(Value::Number(result)))
+            },
+            (Self::Formula(products), Self::Known(rhs)) => {
+                let rhs = rhs.as_number()?;
+
+                Self::scale(products, |coefficient| apply(coefficient, rhs))
+            },
+            (Self::Known(lhs), Self::Formula(products)) if !divide => {
+                let lhs = lhs.as_number()?;
+
+                Self::scale(products, |coefficient| apply(lhs, coefficient))
+            },
+            (lhs, rhs) => {
+                let (lhs, rhs) = (lhs.product()?, rhs.product()?);
+                let flipped = rhs
+                    .factors
+                    .into_iter()
+                    .map(|(divides, factor)| (divides != divide, factor));
+
+                Ok(Self::Formula(vec![Product {
+                    coefficient: apply(lhs.coefficient, rhs.coefficient)?,
+                    factors: lhs.factors.into_iter().chain(flipped).collect(),
+                }]))
+            },
+        }
+    }
+
+    fn scale(
+        products: Vec<Product>,
+        apply: impl Fn(Number) -> Result<Number>,
+    ) -> Result<Self> {
+        let products = products
+            .into_iter()
+            .map(|product| {
+                Ok(Product {
+                    coefficient: apply(product.coefficient)?,
+                    factors: product.factors,
+                })
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(Self::Formula(products))
+    }
+
+    fn products(self) -> Result<Vec<Product>> {
+        match self {
+            Self::Known(value) => Ok(vec![Product {
+                coefficient: value.as_number()?,
+                factors: vec![],
+            }]),
+            Self::Formula(products) => Ok(products),
+        }
+    }
+
+    /// The residual as a single product, parenthesizing sums.
+    fn product(self) -> Result<Product> {
+        let mut products = self.products()?;
+
+        if products.len() == 1 {
+            return Ok(products.remove(0));
+        }
+
+        Ok(Product {
+            coefficient: Number::Int(1),
+            factors: vec![(false, format!("({})", Self::Formula(products)))],
+        })
+    }
+}
+
+fn negate(number: Number) -> Result<Number> {
+    Number::Int(0).checked_sub(number)
+}
+
+impl fmt::Display for Residual {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        let products = match self {
+            Self::Known(value) => return write!(formatter, "{}", value),
+            Self::Formula(products) => products,
+        };
+
+        for (index, product) in products.iter().enumerate() {
+            let negative = product.coefficient < Number::Int(0);
+
+            match (index, negative) {
+                (0, _) => write!(formatter, "{}", product)?,
+                (_, false) => write!(formatter, " + {}", product)?,
+                (_, true) => {
+                    let positive = Product {
+                        coefficient: negate(product.coefficient)
+                            .map_err(|_| fmt::Error)?,
+                        factors: product.factors.clone(),
+                    };
+
+                    write!(formatter, " - {}", positive)?
+                },
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl fmt::Display for Product {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        let mut factors = self.factors.iter();
+
+        match factors.next() {
+            None => return write!(formatter, "{}", self.coefficient),
+            // The coefficient of one is implied.
+            Some((false, factor)) if self.coefficient == Number::Int(1) => {
+                write!(formatter, "{}", factor)?
+            },
+            Some((divides, factor)) => {
+                let operator = if *divides { "/" } else { "*" };
+
+                write!(
+                    formatter,
+                    "{} {} {}",
+                    self.coefficient, operator, factor
+                )?
+            },
+        }
+
+        for (divides, factor) in factors {
+            let operator = if *divides { "/" } else { "*" };
+
+            write!(formatter, " {} {}", operator, factor)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn known(number: Number) -> Residual {
+        Residual::Known(Value::Number(number))
+    }
+
+    #[test]
+    fn test_folding() {
+        let context = &Context::default();
+        let e = Residual::symbol("E".into());
+
+        // 2 + 2 * E / 10
+        let term = known(2.into()).multiply(e.clone(), false, context);
+        let term = term.unwrap().multiply(known(10.into()), true, context);
+        let sum = known(2.into()).add(term.unwrap(), false, context).unwrap();
+
+        assert_eq!("2 + 0.2 * E", sum.to_string());
+
+        let difference = sum.add(known(2.into()), true, context).unwrap();
+        let difference = known(1.into()).add(difference, true, context);
+
+        assert_eq!("1 - 0.2 * E", difference.unwrap().to_string());
+    }
+
+    #[test]
+    fn test_products_of_formulas() {
+        let context = &Context::default();
+        let e = Residual::symbol("E".into());
+        let f = Residual::symbol("F".into());
+
+        let sum = e.clone().add(known(1.into()), false, context).unwrap();
+        let product = f.clone().multiply(sum, true, context).unwrap();
+
+        assert_eq!("F / (E + 1)", product.to_string());
+
+        let quotient = known(3.into()).multiply(e, true, context).unwrap();
+        let quotient = quotient.multiply(f, true, context).unwrap();
+
+        assert_eq!("3 / E / F", quotient.to_string());
+    }
+}
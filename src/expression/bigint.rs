@@ -0,0 +1,326 @@
+/// This module implements arbitrary precision integers, just enough of
+/// them to keep rationals exact.
+use std::cmp::Ordering;
+use std::fmt;
+
+/// Limbs are stored little-endian, without trailing zeroes, so that every
+/// number has a single representation. Zero is never negative.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct BigInt {
+    negative: bool,
+    limbs: Vec<u32>,
+}
+
+impl BigInt {
+    pub fn zero() -> Self {
+        Self::from_parts(false, vec![])
+    }
+
+    fn from_parts(negative: bool, mut limbs: Vec<u32>) -> Self {
+        while limbs.last() == Some(&0) {
+            limbs.pop();
+        }
+
+        let negative = negative && !limbs.is_empty();
+
+        Self { negative, limbs }
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.limbs.is_empty()
+    }
+
+    pub fn is_negative(&self) -> bool {
+        self.negative
+    }
+
+    pub fn abs(&self) -> Self {
+        Self::from_parts(false, self.limbs.clone())
+    }
+
+    pub fn negate(&self) -> Self {
+        Self::from_parts(!self.negative, self.limbs.clone())
+    }
+
+    pub fn pow10(exponent: usize) -> Self {
+        (0..exponent).fold(Self::from(1), |acc, _| acc.mul(&Self::from(10)))
+    }
+
+    pub fn add(&self, rhs: &Self) -> Self {
+        if self.negative == rhs.negative {
+            return Self::from_parts(
+                self.negative,
+                add(&self.limbs, &rhs.limbs),
+            );
+        }
+
+        match compare(&self.limbs, &rhs.limbs) {
+            Ordering::Less => Self::from_parts(
+                rhs.negative,
+                subtract(&rhs.limbs, &self.limbs),
+            ),
+            _ => Self::from_parts(
+                self.negative,
+                subtract(&self.limbs, &rhs.limbs),
+            ),
+        }
+    }
+
+    pub fn sub(&self, rhs: &Self) -> Self {
+        self.add(&rhs.negate())
+    }
+
+    pub fn mul(&self, rhs: &Self) -> Self {
+        let mut limbs = vec![0u32; self.limbs.len() + rhs.limbs.len()];
+
+        for (i, &lhs) in self.limbs.iter().enumerate() {
+            let mut carry = 0u64;
+
+            for (j, &rhs) in rhs.limbs.iter().enumerate() {
+                let sum = lhs as u64 * rhs as u64 + limbs[i + j] as u64 + carry;
+                limbs[i + j] = sum as u32;
+                carry = sum >> 32;
+            }
+
+            limbs[i + rhs.limbs.len()] = carry as u32;
+        }
+
+        Self::from_parts(self.negative != rhs.negative, limbs)
+    }
+
+    /// Truncating division, so the remainder has the sign of the dividend.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the divisor is zero.
+    pub fn div_rem(&self, rhs: &Self) -> (Self, Self) {
+        assert!(!rhs.is_zero(), "BigInt division by zero");
+
+        let (quotient, remainder) = div_rem(&self.limbs, &rhs.limbs);
+
+        (
+            Self::from_parts(self.negative != rhs.negative, quotient),
+            Self::from_parts(self.negative, remainder),
+        )
+    }
+
+    pub fn gcd(&self, rhs: &Self) -> Self {
+        let (mut lhs, mut rhs) = (self.abs(), rhs.abs());
+
+        while !rhs.is_zero() {
+            let remainder = lhs.div_rem(&rhs).1;
+            lhs = rhs;
+            rhs = remainder;
+        }
+
+        lhs
+    }
+
+    pub fn bit_length(&self) -> usize {
+        match self.limbs.last() {
+            Some(last) => self.limbs.len() * 32 - last.leading_zeros() as usize,
+            None => 0,
+        }
+    }
+
+    pub fn shl(&self, bits: usize) -> Self {
+        let mut limbs = vec![0u32; bits / 32];
+        let shift = bits % 32;
+        let mut carry = 0u32;
+
+        for &limb in &self.limbs {
+            if shift == 0 {
+                limbs.push(limb);
+            } else {
+                limbs.push(limb << shift | carry);
+                carry = limb >> (32 - shift);
+            }
+        }
+
+        limbs.push(carry);
+
+        Self::from_parts(self.negative, limbs)
+    }
+
+    /// The nearest float, give or take a rounding of the lowest bits.
+    pub fn to_f64(&self) -> f64 {
+        let magnitude = self
+            .limbs
+            .iter()
+            .rev()
+            .fold(0.0, |acc, &limb| acc * 4_294_967_296.0 + limb as f64);
+
+        if self.negative {
+            -magnitude
+        } else {
+            magnitude
+        }
+    }
+}
+
+fn compare(lhs: &[u32], rhs: &[u32]) -> Ordering {
+    lhs.len()
+        .cmp(&rhs.len())
+        .then_with(|| lhs.iter().rev().cmp(rhs.iter().rev()))
+}
+
+fn add(lhs: &[u32], rhs: &[u32]) -> Vec<u32> {
+    let mut limbs = Vec::with_capacity(lhs.len().max(rhs.len()) + 1);
+    let mut carry = 0u64;
+
+    for i in 0..lhs.len().max(rhs.len()) {
+        let sum = *lhs.get(i).unwrap_or(&0) as u64
+            + *rhs.get(i).unwrap_or(&0) as u64
+            + carry;
+        limbs.push(sum as u32);
+        carry = sum >> 32;
+    }
+
+    limbs.push(carry as u32);
+
+    limbs
+}
+
+/// Subtracts magnitudes, the left one being the larger.
+fn subtract(lhs: &[u32], rhs: &[u32]) -> Vec<u32> {
+    let mut limbs = Vec::with_capacity(lhs.len());
+    let mut borrow = 0i64;
+
+    for (i, &limb) in lhs.iter().enumerate() {
+        let mut difference =
+            limb as i64 - *rhs.get(i).unwrap_or(&0) as i64 - borrow;
+        borrow = 0;
+
+        if difference < 0 {
+            difference += 1 << 32;
+            borrow = 1;
+        }
+
+        limbs.push(difference as u32);
+    }
+
+    limbs
+}
+
+/// Long division, one bit at a time. Slow, but simple, and the numbers we
+/// deal with are short anyway.
+fn div_rem(lhs: &[u32], rhs: &[u32]) -> (Vec<u32>, Vec<u32>) {
+    let mut quotient = vec![0u32; lhs.len()];
+    let mut remainder = BigInt::zero();
+    let divisor = BigInt::from_parts(false, rhs.to_vec());
+
+    for bit in (0..lhs.len() * 32).rev() {
+        remainder = remainder.shl(1);
+
+        if lhs[bit / 32] >> (bit % 32) & 1 == 1 {
+            remainder = remainder.add(&BigInt::from(1));
+        }
+
+        if compare(&remainder.limbs, rhs) != Ordering::Less {
+            remainder = remainder.sub(&divisor);
+            quotient[bit / 32] |= 1 << (bit % 32);
+        }
+    }
+
+    (quotient, remainder.limbs)
+}
+
+impl From<i64> for BigInt {
+    fn from(value: i64) -> Self {
+        let magnitude = value.unsigned_abs();
+
+        Self::from_parts(
+            value < 0,
+            vec![magnitude as u32, (magnitude >> 32) as u32],
+        )
+    }
+}
+
+impl Ord for BigInt {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self.negative, other.negative) {
+            (false, true) => Ordering::Greater,
+            (true, false) => Ordering::Less,
+            (false, false) => compare(&self.limbs, &other.limbs),
+            (true, true) => compare(&other.limbs, &self.limbs),
+        }
+    }
+}
+
+impl PartialOrd for BigInt {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl fmt::Display for BigInt {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        // Peel off nine decimal digits at a time.
+        let chunk = Self::from(1_000_000_000);
+        let mut chunks = vec![];
+        let mut rest = self.abs();
+
+        while !rest.is_zero() {
+            let (quotient, remainder) = rest.div_rem(&chunk);
+            chunks.push(remainder.limbs.first().copied().unwrap_or(0));
+            rest = quotient;
+        }
+
+        if self.negative {
+            formatter.write_str("-")?;
+        }
+
+        match chunks.split_last() {
+            Some((head, tail)) => {
+                write!(formatter, "{}", head)?;
+
+                for chunk in tail.iter().rev() {
+                    write!(formatter, "{:09}", chunk)?;
+                }
+
+                Ok(())
+            },
+            None => formatter.write_str("0"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_arithmetic() {
+        let big = BigInt::from(i64::MAX).mul(&BigInt::from(i64::MIN));
+
+        assert_eq!("-85070591730234615856620279821087277056", big.to_string());
+        assert_eq!(
+            "-85070591730234615847396907784232501249",
+            big.add(&BigInt::from(i64::MAX)).to_string()
+        );
+        assert_eq!("-7", BigInt::from(3).sub(&BigInt::from(10)).to_string());
+        assert_eq!("1000000000000000000000", BigInt::pow10(21).to_string());
+    }
+
+    #[test]
+    fn test_division() {
+        let (quotient, remainder) = BigInt::pow10(30)
+            .add(&BigInt::from(7))
+            .div_rem(&BigInt::pow10(20));
+
+        assert_eq!(BigInt::pow10(10), quotient);
+        assert_eq!(BigInt::from(7), remainder);
+
+        let (quotient, remainder) = BigInt::from(-7).div_rem(&BigInt::from(2));
+
+        assert_eq!((BigInt::from(-3), BigInt::from(-1)), (quotient, remainder));
+        assert_eq!(BigInt::from(6), BigInt::from(-48).gcd(&BigInt::from(18)));
+    }
+
+    #[test]
+    fn test_ordering() {
+        assert!(BigInt::from(-5) < BigInt::from(3));
+        assert!(BigInt::pow10(20) > BigInt::from(i64::MAX));
+        assert_eq!(BigInt::zero(), BigInt::from(5).sub(&BigInt::from(5)));
+    }
+}
@@ -1,20 +1,34 @@
+mod comparison;
 mod expression;
 mod factor;
 
 use nom::bytes::complete::tag;
-use nom::character::complete::{alpha1, space0};
+use nom::character::complete::space0;
 use nom::combinator::map;
 use nom::sequence::{delimited, pair, preceded};
 use nom::IResult;
 
+pub(crate) use self::comparison::ComparisonOperator;
 pub use self::expression::Expression;
-use crate::expression::common::Expression as ExpressionTrait;
+pub(crate) use self::expression::{
+    ExpressionOperator, Implication, ImplicationOperator, Term, TermOperator,
+};
+pub(crate) use self::factor::Factor;
+use crate::expression::common::{
+    identifier, token, Expression as ExpressionTrait,
+};
 
 /// Parses the whole boolean expression, such as `A && B && !C => H = M`.
 pub fn parse(input: &str) -> IResult<&str, (Expression, String)> {
     pair(Expression::parse, parse_matcher_clause)(input)
 }
 
+/// Parses a default rule, such as `else => H = NONE`, which applies when
+/// no other boolean expression is truthy.
+pub fn parse_default(input: &str) -> IResult<&str, String> {
+    preceded(pair(token("else"), space0), parse_matcher_clause)(input)
+}
+
 /// Parses a matcher clause, such as `=> H = M` and returns the
 /// value of H corresponding to the clause (M in the case of the example).
 fn parse_matcher_clause(input: &str) -> IResult<&str, String> {
@@ -22,7 +36,7 @@ fn parse_matcher_clause(input: &str) -> IResult<&str, String> {
 }
 
 fn variable(input: &str) -> IResult<&str, String> {
-    map(delimited(space0, alpha1, space0), String::from)(input)
+    map(delimited(space0, identifier, space0), String::from)(input)
 }
 
 #[cfg(test)]
@@ -43,4 +57,12 @@ mod test {
 
         assert_eq!(result.1, "M");
     }
+
+    #[test]
+    fn test_default_parsing() {
+        assert_eq!(Ok(("", "NONE".into())), parse_default("else => H = NONE"));
+        assert_eq!(Ok(("", "NONE".into())), parse_default("ELSE=> H = NONE"));
+        assert!(parse_default("elsewhere => H = NONE").is_err());
+        assert!(parse("else => H = NONE").is_err());
+    }
 }
@@ -0,0 +1,126 @@
+/// This module is used to parse comparison operators, which turn a pair of
+/// arithmetic expressions into a boolean factor, such as `D > 100`.
+use std::fmt;
+use std::str::FromStr;
+
+use anyhow::Result;
+
+use nom::branch::alt;
+use nom::IResult;
+
+use crate::expression::common::{token, Operator};
+use crate::expression::value::Value;
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum ComparisonOperator {
+    Equal,
+    NotEqual,
+    LessOrEqual,
+    GreaterOrEqual,
+    Less,
+    Greater,
+}
+
+impl ComparisonOperator {
+    pub fn compare<T: PartialOrd>(&self, lhs: T, rhs: T) -> bool {
+        match self {
+            Self::Equal => lhs == rhs,
+            Self::NotEqual => lhs != rhs,
+            Self::LessOrEqual => lhs <= rhs,
+            Self::GreaterOrEqual => lhs >= rhs,
+            Self::Less => lhs < rhs,
+            Self::Greater => lhs > rhs,
+        }
+    }
+
+    /// Compares values of the same type. Only numbers are ordered, while
+    /// booleans and strings can only be checked for (in)equality.
+    pub fn compare_values(&self, lhs: Value, rhs: Value) -> Result<bool> {
+        match (lhs, rhs) {
+            (Value::Bool(lhs), Value::Bool(rhs)) if self.is_equality() => {
+                Ok(self.compare(lhs, rhs))
+            },
+            (Value::String(lhs), Value::String(rhs)) if self.is_equality() => {
+                Ok(self.compare(lhs, rhs))
+            },
+            (lhs, rhs) => Ok(self.compare(lhs.as_number()?, rhs.as_number()?)),
+        }
+    }
+
+    /// Whether the operator checks for (in)equality rather than ordering.
+    pub fn is_equality(&self) -> bool {
+        matches!(self, Self::Equal | Self::NotEqual)
+    }
+}
+
+impl FromStr for ComparisonOperator {
+    type Err = anyhow::Error;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        match input {
+            "==" => Ok(Self::Equal),
+            "!=" | "≠" => Ok(Self::NotEqual),
+            "<=" | "≤" => Ok(Self::LessOrEqual),
+            ">=" | "≥" => Ok(Self::GreaterOrEqual),
+            "<" => Ok(Self::Less),
+            ">" => Ok(Self::Greater),
+            _ => anyhow::bail!("Operator {} is not recognized", input),
+        }
+    }
+}
+
+impl fmt::Display for ComparisonOperator {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        let operator = match self {
+            Self::Equal => "==",
+            Self::NotEqual => "!=",
+            Self::LessOrEqual => "<=",
+            Self::GreaterOrEqual => ">=",
+            Self::Less => "<",
+            Self::Greater => ">",
+        };
+
+        formatter.write_str(operator)
+    }
+}
+
+impl Operator for ComparisonOperator {
+    fn parser() -> Box<dyn Fn(&str) -> IResult<&str, &str>> {
+        // Two-character operators go first, so that `<=` is not taken for
+        // `<` followed by garbage.
+        Box::new(|input: &str| {
+            alt((
+                token("=="),
+                token("!="),
+                token("≠"),
+                token("<="),
+                token("≤"),
+                token(">="),
+                token("≥"),
+                token("<"),
+                token(">"),
+            ))(input)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parsing_operator() {
+        assert_eq!(
+            Ok(("1", ComparisonOperator::LessOrEqual)),
+            ComparisonOperator::parse("<=1")
+        );
+        assert_eq!(
+            Ok(("1", ComparisonOperator::Less)),
+            ComparisonOperator::parse("<1")
+        );
+        assert_eq!(
+            Ok(("1", ComparisonOperator::NotEqual)),
+            ComparisonOperator::parse("≠1")
+        );
+    }
+}
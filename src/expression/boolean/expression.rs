@@ -1,26 +1,193 @@
+//! Boolean operators, from the tightest binding to the loosest one:
+//!
+//! 1. `&&`, `||` and `^^` (`xor`) share the precedence, so `A || B && C`
+//!    means `(A || B) && C`.
+//! 2. `->`, the implication.
+//! 3. `<->`, the equivalence.
+//!
+//! Operators of the same level are applied left to right, which makes
+//! `A -> B -> C` mean `(A -> B) -> C`. Use parentheses when in doubt.
+//!
+//! `&&` and `||` may also be spelled as `and` / `∧` and `or` / `∨`.
 use std::str::FromStr;
 
+use anyhow::Result;
+
 use super::factor::Factor;
 use crate::expression;
+use crate::expression::common::{
+    Context, Expression as ExpressionTrait, ExpressionWithOperator, Operator,
+    Precedence,
+};
+use crate::expression::optimize::Identities;
+use crate::expression::truth::{PartiallyReducible, Truth};
+use crate::expression::value::Value;
 
 // Represents a term, which can consists of one, or multiple factors which
-// can be divided or multiplied.
-expression!(Expression<bool, Factor> | ExpressionOperator: And => &&, Or => ||);
+// are conjuncted, disjuncted or xored.
+expression!(Term<bool, Factor> | TermOperator(Bool):
+    And("&&", "and", "∧") => |lhs, rhs| Ok(lhs && rhs),
+    Or("||", "or", "∨") => |lhs, rhs| Ok(lhs || rhs),
+    Xor("^^", "xor") => |lhs, rhs| Ok(lhs ^ rhs));
+
+// Represents an implication, which can consists of one, or multiple terms.
+expression!(Implication<bool, Term> | ImplicationOperator(Bool):
+    Implies("->") => |lhs, rhs| Ok(!lhs || rhs));
+
+// Represents an expression, which can consists of one, or multiple
+// implications which are checked for equivalence.
+expression!(Expression<bool, Implication> | ExpressionOperator(Bool):
+    Iff("<->") => |lhs, rhs| Ok(lhs == rhs));
+
+impl Precedence for TermOperator {
+    const PRECEDENCE: u8 = 3;
+}
+
+impl Precedence for ImplicationOperator {
+    const PRECEDENCE: u8 = 2;
+}
+
+impl Precedence for ExpressionOperator {
+    const PRECEDENCE: u8 = 1;
+}
+
+/// Operators which can be applied to partially known values.
+pub trait KleeneOperator {
+    fn apply_kleene(&self, lhs: Truth, rhs: Truth) -> Truth;
+}
+
+impl KleeneOperator for TermOperator {
+    fn apply_kleene(&self, lhs: Truth, rhs: Truth) -> Truth {
+        match self {
+            Self::And => lhs.and(rhs),
+            Self::Or => lhs.or(rhs),
+            Self::Xor => lhs.xor(rhs),
+        }
+    }
+}
+
+impl KleeneOperator for ImplicationOperator {
+    fn apply_kleene(&self, lhs: Truth, rhs: Truth) -> Truth {
+        match self {
+            Self::Implies => lhs.implies(rhs),
+        }
+    }
+}
+
+impl KleeneOperator for ExpressionOperator {
+    fn apply_kleene(&self, lhs: Truth, rhs: Truth) -> Truth {
+        match self {
+            Self::Iff => lhs.iff(rhs),
+        }
+    }
+}
+
+/// Applies the boolean operation to constants.
+fn fold(
+    lhs: Value,
+    rhs: Value,
+    apply: fn(bool, bool) -> bool,
+) -> Option<Value> {
+    match (lhs, rhs) {
+        (Value::Bool(lhs), Value::Bool(rhs)) => Some(apply(lhs, rhs).into()),
+        _ => None,
+    }
+}
+
+impl Identities for TermOperator {
+    fn fold(&self, lhs: Value, rhs: Value, _: &Context) -> Option<Value> {
+        match self {
+            Self::And => fold(lhs, rhs, |lhs, rhs| lhs && rhs),
+            Self::Or => fold(lhs, rhs, |lhs, rhs| lhs || rhs),
+            Self::Xor => fold(lhs, rhs, |lhs, rhs| lhs ^ rhs),
+        }
+    }
+
+    fn is_right_identity(&self, constant: &Value) -> bool {
+        *constant == Value::Bool(*self == Self::And)
+    }
+
+    fn is_left_identity(&self, constant: &Value) -> bool {
+        self.is_right_identity(constant)
+    }
+
+    fn absorbs(&self, constant: &Value) -> bool {
+        match self {
+            Self::And => *constant == Value::Bool(false),
+            Self::Or => *constant == Value::Bool(true),
+            Self::Xor => false,
+        }
+    }
+
+    fn complement(&self) -> Option<Value> {
+        Some(Value::Bool(*self != Self::And))
+    }
+}
+
+impl Identities for ImplicationOperator {
+    fn fold(&self, lhs: Value, rhs: Value, _: &Context) -> Option<Value> {
+        fold(lhs, rhs, |lhs, rhs| !lhs || rhs)
+    }
+
+    fn is_right_identity(&self, _: &Value) -> bool {
+        false
+    }
+
+    fn is_left_identity(&self, constant: &Value) -> bool {
+        *constant == Value::Bool(true)
+    }
+
+    fn absorbs(&self, constant: &Value) -> bool {
+        *constant == Value::Bool(true)
+    }
+}
+
+impl Identities for ExpressionOperator {
+    fn fold(&self, lhs: Value, rhs: Value, _: &Context) -> Option<Value> {
+        fold(lhs, rhs, |lhs, rhs| lhs == rhs)
+    }
+
+    fn is_right_identity(&self, constant: &Value) -> bool {
+        *constant == Value::Bool(true)
+    }
+
+    fn is_left_identity(&self, constant: &Value) -> bool {
+        *constant == Value::Bool(true)
+    }
+
+    fn complement(&self) -> Option<Value> {
+        Some(Value::Bool(false))
+    }
+}
+
+impl<T, O> PartiallyReducible for ExpressionWithOperator<T, O>
+where
+    T: ExpressionTrait + PartiallyReducible,
+    O: Operator + KleeneOperator,
+{
+    fn reduce_partially(&self, context: &Context) -> Result<Truth> {
+        let init = self.head.reduce_partially(context)?;
+
+        self.tail.iter().try_fold(init, |acc, (operator, operand)| {
+            let rhs = operand.reduce_partially(context)?;
+
+            Ok(operator.apply_kleene(acc, rhs))
+        })
+    }
+}
 
 #[cfg(test)]
 mod tests {
-    use std::collections::HashMap;
-
     use super::*;
-    use crate::expression::common::{Expression as ExpressionTrait, Reducible};
+    use crate::expression::common::Reducible;
 
     #[test]
     fn test_expression_reducibility() {
         let expression_str = "UNKNOWN || false";
         let expression = Expression::parse(expression_str).unwrap().1;
-        let mut variables_table = HashMap::new();
-        variables_table.insert("UNKNOWN".into(), true);
-        let actual = expression.reduce(&variables_table).unwrap();
+        let mut context = Context::default();
+        context.variables.insert("UNKNOWN".into(), true.into());
+        let actual = expression.reduce(&context).unwrap();
 
         assert_eq!(true, actual);
     }
@@ -30,12 +197,141 @@ mod tests {
         let expression_str = "!UNKNOWN || UNKNOWN && true || false && VALUE";
         let expression = Expression::parse(expression_str).unwrap().1;
 
-        let mut variables = HashMap::new();
-        variables.insert("UNKNOWN".into(), true);
-        variables.insert("VALUE".into(), false);
+        let mut context = Context::default();
+        context.variables.insert("UNKNOWN".into(), true.into());
+        context.variables.insert("VALUE".into(), false.into());
 
-        let actual = expression.reduce(&variables).unwrap();
+        let actual = expression.reduce(&context).unwrap();
 
         assert_eq!(false, actual);
     }
+
+    #[test]
+    fn test_exactly_one_of() {
+        let expression = Expression::parse("A xor B").unwrap().1;
+        let mut context = Context::default();
+
+        for &(a, b, expected) in &[
+            (false, false, false),
+            (true, false, true),
+            (false, true, true),
+            (true, true, false),
+        ] {
+            context.variables.insert("A".into(), a.into());
+            context.variables.insert("B".into(), b.into());
+
+            assert_eq!(expected, expression.reduce(&context).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_operators_precedence() {
+        let expression_str = "A && B -> C <-> !A || C";
+        let expression = Expression::parse(expression_str).unwrap().1;
+        let mut context = Context::default();
+        context.variables.insert("A".into(), true.into());
+        context.variables.insert("B".into(), true.into());
+        context.variables.insert("C".into(), false.into());
+
+        // ((A && B) -> C) <-> (!A || C), i.e. false <-> false
+        assert_eq!(true, expression.reduce(&context).unwrap());
+        assert!(Expression::parse("A -> B").unwrap().1.tail.is_empty());
+    }
+
+    #[test]
+    fn test_operator_aliases() {
+        let expression_str = "A AND not B or C ∧ ¬A";
+        let expression = Expression::parse(expression_str).unwrap().1;
+        let mut context = Context::default();
+        context.variables.insert("A".into(), true.into());
+        context.variables.insert("B".into(), false.into());
+        context.variables.insert("C".into(), true.into());
+
+        assert_eq!("", Expression::parse(expression_str).unwrap().0);
+        assert_eq!(false, expression.reduce(&context).unwrap());
+        assert!(Expression::parse("A andromeda")
+            .unwrap()
+            .1
+            .head
+            .tail
+            .is_empty());
+
+        for &source in &["B or_x", "B or.x"] {
+            let (rest, expression) = Expression::parse(source).unwrap();
+
+            assert_eq!(&source[2..], rest);
+            assert!(expression.head.head.tail.is_empty());
+        }
+    }
+
+    #[test]
+    fn test_partially_known_expression() {
+        let mut context = Context::default();
+        context.variables.insert("A".into(), false.into());
+
+        for &(source, expected) in &[
+            ("A && B", Truth::False),
+            ("A || B", Truth::Unknown),
+            ("A -> B", Truth::True),
+            ("!A && (B || !B)", Truth::Unknown),
+            ("A <-> false", Truth::True),
+        ] {
+            let expression = Expression::parse(source).unwrap().1;
+
+            assert_eq!(
+                expected,
+                expression.reduce_partially(&context).unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn test_canonical_form() {
+        for &(source, expected) in &[
+            ("!(A) and (B or C)", "!A && (B || C)"),
+            ("(A && B) || C", "A && B || C"),
+            ("A -> (B -> C)", "A -> (B -> C)"),
+            ("(A -> B) -> C", "A -> B -> C"),
+            ("not (A <-> B)", "!(A <-> B)"),
+            ("!((D > 1)) || !(false)", "!(D > 1) || true"),
+            ("(D+1)>2 ∧ D in [1,(2)]", "D + 1 > 2 && D in [1, 2]"),
+        ] {
+            let expression = Expression::parse(source).unwrap().1;
+
+            assert_eq!(expected, expression.to_string());
+        }
+    }
+
+    #[test]
+    fn test_optimization() {
+        use crate::expression::optimize::Optimize;
+
+        let context = Context::default();
+
+        for &(source, expected) in &[
+            ("A && true", "A"),
+            ("true && A || false", "A"),
+            ("A || !A", "true"),
+            ("!!A && !(!B)", "A && B"),
+            ("A || !A && C", "C"),
+            ("A && B && false || C", "C"),
+            ("(A) -> true <-> 1 + 1 == 2", "true"),
+            ("A ^^ false -> B", "A -> B"),
+        ] {
+            let expression = Expression::parse(source).unwrap().1;
+
+            assert_eq!(expected, expression.optimize(&context).to_string());
+        }
+    }
+
+    #[test]
+    fn test_parenthesized_expression() {
+        let expression_str = "!(A -> B) && (B ^^ true)";
+        let expression = Expression::parse(expression_str).unwrap().1;
+        let mut context = Context::default();
+        context.variables.insert("A".into(), true.into());
+        context.variables.insert("B".into(), false.into());
+
+        assert_eq!(true, expression.reduce(&context).unwrap());
+    }
 }
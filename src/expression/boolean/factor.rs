@@ -1,89 +1,602 @@
 /// This module is used to parse variables in boolean expressions,
-/// including constants (true / false).
-use std::collections::HashMap;
+/// including constants (true / false), comparisons and set membership
+/// tests of arithmetic expressions.
+use std::fmt;
 
-use anyhow::{Context, Result};
+use anyhow::Result;
 
 use nom::branch::alt;
 use nom::bytes::complete::tag;
-use nom::character::complete::{alpha1, space0};
+use nom::character::complete::{char, space0};
 use nom::combinator::{map, opt};
-use nom::sequence::{delimited, pair};
+use nom::multi::separated_list;
+use nom::sequence::{delimited, pair, preceded, terminated, tuple};
 use nom::IResult;
+use serde_json::Value as Json;
 
-use crate::expression::common::{Expression, Reducible};
+use super::comparison::ComparisonOperator;
+use super::expression::Expression as BooleanExpression;
+use super::expression::{
+    ExpressionOperator, Implication, ImplicationOperator, Term, TermOperator,
+};
+use crate::expression::arithmetic::Expression as ArithmeticExpression;
+use crate::expression::common::{
+    token, unparenthesized, Canonical, Context, Expression,
+    ExpressionWithOperator, Identifier, Nested, Operator, Parenthesized,
+    Position, Reducible, Span, Variables,
+};
+use crate::expression::json::{self, FromNode, Node, ToNode};
+use crate::expression::optimize::{Operand, Optimize};
+use crate::expression::truth::{PartiallyReducible, Truth};
+use crate::expression::typing::{
+    expect, unify, Type, TypeCheck, TypeError, Types,
+};
+use crate::expression::value::Value;
 
-/// An enumeration representing either a variable or a constant number.
-#[derive(PartialEq, Debug)]
+/// An enumeration representing either a variable, a constant, a
+/// comparison of two arithmetic expressions or a parenthesized expression.
+#[derive(Clone, PartialEq, Debug)]
 pub enum Factor {
-    Variable(String),
-    NegatedVariable(String),
+    Variable(Identifier),
+    NegatedVariable(Identifier),
     Constant(bool),
+    ExpressionInParens(Box<BooleanExpression>),
+    /// Negation of anything but a constant or a variable, such as `!(A)`.
+    Negation(Box<Factor>),
+    Comparison(
+        ArithmeticExpression,
+        ComparisonOperator,
+        ArithmeticExpression,
+    ),
+    /// Set membership, such as `country in ["DE", "AT", "CH"]`.
+    Membership(ArithmeticExpression, Vec<ArithmeticExpression>),
 }
 
 impl Reducible<bool> for Factor {
-    fn reduce(&self, variables_table: &HashMap<String, bool>) -> Result<bool> {
-        fn fetch_variable(
-            variable: &String,
-            table: &HashMap<String, bool>,
-            negated: bool,
-        ) -> Result<bool> {
-            table
-                .get(variable)
-                .map(|&val| if negated { !val } else { val })
-                .with_context(|| format!("Variable {} is undefined", variable))
+    fn reduce(&self, context: &Context) -> Result<bool> {
+        match self {
+            Self::Constant(constant) => Ok(*constant),
+            Self::Variable(identifier) => {
+                context.get(&identifier.name)?.as_bool()
+            },
+            Self::NegatedVariable(identifier) => {
+                context.get(&identifier.name)?.as_bool().map(|value| !value)
+            },
+            Self::ExpressionInParens(expression) => expression.reduce(context),
+            Self::Negation(factor) => {
+                factor.reduce(context).map(|value| !value)
+            },
+            Self::Comparison(lhs, operator, rhs) => operator
+                .compare_values(lhs.reduce(context)?, rhs.reduce(context)?),
+            Self::Membership(item, set) => {
+                let item: Value = item.reduce(context)?;
+
+                for element in set {
+                    let element = element.reduce(context)?;
+
+                    if ComparisonOperator::Equal
+                        .compare_values(item.clone(), element)?
+                    {
+                        return Ok(true);
+                    }
+                }
+
+                Ok(false)
+            },
         }
+    }
+}
 
+/// Evaluates the factor under Kleene logic, so that missing boolean
+/// variables are unknown rather than errors. So are comparisons and set
+/// membership tests which refer to missing variables.
+impl PartiallyReducible for Factor {
+    fn reduce_partially(&self, context: &Context) -> Result<Truth> {
         match self {
-            Self::Constant(constant) => Ok(*constant),
-            Self::Variable(string) => {
-                fetch_variable(string, variables_table, false)
+            Self::Variable(identifier) => match context.get(&identifier.name) {
+                Ok(value) => value.as_bool().map(Truth::from),
+                Err(_) => Ok(Truth::Unknown),
+            },
+            Self::NegatedVariable(identifier) => {
+                Self::Variable(identifier.name.as_str().into())
+                    .reduce_partially(context)
+                    .map(|value| !value)
+            },
+            Self::ExpressionInParens(expression) => {
+                expression.reduce_partially(context)
+            },
+            Self::Negation(factor) => {
+                factor.reduce_partially(context).map(|value| !value)
+            },
+            _ => {
+                let value: Result<bool> = self.reduce(context);
+                let mut names = vec![];
+                self.variables(&mut names);
+
+                match value {
+                    Ok(value) => Ok(value.into()),
+                    Err(_)
+                        if names
+                            .iter()
+                            .any(|name| context.get(name).is_err()) =>
+                    {
+                        Ok(Truth::Unknown)
+                    },
+                    Err(error) => Err(error),
+                }
+            },
+        }
+    }
+}
+
+impl Operand for Factor {
+    fn constant(&self) -> Option<Value> {
+        match self {
+            Self::Constant(constant) => Some(Value::Bool(*constant)),
+            _ => None,
+        }
+    }
+
+    fn from_constant(value: Value) -> Option<Self> {
+        value.as_bool().ok().map(Self::Constant)
+    }
+
+    fn is_negation_of(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::Variable(lhs), Self::NegatedVariable(rhs))
+            | (Self::NegatedVariable(lhs), Self::Variable(rhs)) => lhs == rhs,
+            (Self::Negation(negated), factor)
+            | (factor, Self::Negation(negated)) => **negated == *factor,
+            _ => false,
+        }
+    }
+}
+
+impl Optimize for Factor {
+    fn optimize(self, context: &Context) -> Self {
+        match self {
+            Self::ExpressionInParens(expression) => {
+                let expression = expression.optimize(context);
+                let implication = &expression.head;
+
+                // Parentheses around a lone factor are redundant.
+                if expression.tail.is_empty()
+                    && implication.tail.is_empty()
+                    && implication.head.tail.is_empty()
+                {
+                    expression.head.head.head
+                } else {
+                    Self::ExpressionInParens(Box::new(expression))
+                }
+            },
+            Self::Negation(factor) => match factor.optimize(context) {
+                Self::Constant(constant) => Self::Constant(!constant),
+                Self::Variable(identifier) => Self::NegatedVariable(identifier),
+                Self::NegatedVariable(identifier) => Self::Variable(identifier),
+                Self::Negation(factor) => *factor,
+                factor => Self::Negation(Box::new(factor)),
+            },
+            Self::Comparison(lhs, operator, rhs) => {
+                let factor = Self::Comparison(
+                    lhs.optimize(context),
+                    operator,
+                    rhs.optimize(context),
+                );
+
+                factor.fold(context)
+            },
+            Self::Membership(item, set) => {
+                let set = set
+                    .into_iter()
+                    .map(|element| element.optimize(context))
+                    .collect();
+
+                Self::Membership(item.optimize(context), set).fold(context)
+            },
+            factor => factor,
+        }
+    }
+}
+
+impl Factor {
+    /// Reduces the factor to a constant, if it refers to no variables.
+    fn fold(self, context: &Context) -> Self {
+        let mut names = vec![];
+        self.variables(&mut names);
+
+        if !names.is_empty() {
+            return self;
+        }
+
+        match self.reduce(context) {
+            Ok(constant) => Self::Constant(constant),
+            Err(_) => self,
+        }
+    }
+
+    /// The negation of the factor. Redundant parentheses around the factor
+    /// are left out, and negated constants and variables are folded, so
+    /// that `!(true)` is `false` just as `!true` is.
+    pub fn negated(self) -> Self {
+        match unparenthesized(self, u8::MAX).unwrap_or_else(|factor| factor) {
+            Self::Constant(constant) => Self::Constant(!constant),
+            Self::Variable(identifier) => Self::NegatedVariable(identifier),
+            factor => Self::Negation(Box::new(factor)),
+        }
+    }
+
+    /// The constant, possibly within redundant parentheses.
+    fn unparenthesized(&self) -> Option<bool> {
+        match self {
+            Self::Constant(constant) => Some(*constant),
+            Self::ExpressionInParens(expression) => {
+                let implication = single(expression)?;
+
+                single(single(implication)?)?.unparenthesized()
+            },
+            _ => None,
+        }
+    }
+}
+
+/// The operand of a chain without any operators.
+fn single<T, O>(chain: &ExpressionWithOperator<T, O>) -> Option<&T>
+where
+    T: Expression,
+    O: Operator,
+{
+    if chain.tail.is_empty() {
+        Some(&chain.head)
+    } else {
+        None
+    }
+}
+
+impl Variables for Factor {
+    fn variables<'a>(&'a self, names: &mut Vec<&'a str>) {
+        match self {
+            Self::Constant(_) => {},
+            Self::Variable(identifier) | Self::NegatedVariable(identifier) => {
+                names.push(&identifier.name)
+            },
+            Self::ExpressionInParens(expression) => expression.variables(names),
+            Self::Negation(factor) => factor.variables(names),
+            Self::Comparison(lhs, _, rhs) => {
+                lhs.variables(names);
+                rhs.variables(names);
+            },
+            Self::Membership(item, set) => {
+                item.variables(names);
+
+                for element in set {
+                    element.variables(names);
+                }
+            },
+        }
+    }
+}
+
+impl Canonical for Factor {
+    /// Parentheses are left to the position, so that redundant ones, such
+    /// as in `(A) && B`, are not printed.
+    fn precedence(&self) -> Option<u8> {
+        match self {
+            Self::ExpressionInParens(expression) => expression.precedence(),
+            // Comparisons bind looser than negation only, as in `!(D > 1)`.
+            Self::Comparison(..) | Self::Membership(..) => Some(u8::MAX - 1),
+            _ => None,
+        }
+    }
+
+    fn write(
+        &self,
+        formatter: &mut fmt::Formatter,
+        position: Position,
+    ) -> fmt::Result {
+        match self {
+            Self::Constant(constant) => write!(formatter, "{}", constant),
+            Self::Variable(identifier) => write!(formatter, "{}", identifier),
+            Self::NegatedVariable(identifier) => {
+                write!(formatter, "!{}", identifier)
+            },
+            Self::ExpressionInParens(expression) => {
+                expression.write(formatter, position)
+            },
+            // The parser folds `!(true)` into `false`, so negated constants
+            // are printed folded as well.
+            Self::Negation(factor) if factor.unparenthesized().is_some() => {
+                let constant = factor.unparenthesized().ok_or(fmt::Error)?;
+
+                write!(formatter, "{}", !constant)
+            },
+            Self::Negation(factor) => {
+                formatter.write_str("!")?;
+                factor.write_within(
+                    formatter,
+                    Position::operand(position.followed),
+                )
+            },
+            Self::Comparison(lhs, operator, rhs) => {
+                write!(formatter, "{} {} {}", lhs, operator, rhs)
+            },
+            Self::Membership(item, set) => {
+                let set: Vec<String> =
+                    set.iter().map(ToString::to_string).collect();
+
+                write!(formatter, "{} in [{}]", item, set.join(", "))
+            },
+        }
+    }
+}
+
+impl fmt::Display for Factor {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        self.write(formatter, Position::default())
+    }
+}
+
+impl Parenthesized for Factor {
+    type Root = BooleanExpression;
+
+    fn parenthesized(&self) -> Option<&Self::Root> {
+        match self {
+            Self::ExpressionInParens(expression) => Some(expression),
+            _ => None,
+        }
+    }
+
+    fn into_parenthesized(self) -> Result<Self::Root, Self> {
+        match self {
+            Self::ExpressionInParens(expression) => Ok(*expression),
+            factor => Err(factor),
+        }
+    }
+}
+
+impl Nested<BooleanExpression> for BooleanExpression {
+    fn from_root(root: BooleanExpression) -> Self {
+        root
+    }
+}
+
+impl Nested<BooleanExpression> for Implication {
+    fn from_root(root: BooleanExpression) -> Self {
+        root.into_operand().unwrap_or_else(|root| {
+            Term::from(Factor::ExpressionInParens(Box::new(root))).into()
+        })
+    }
+}
+
+impl Nested<BooleanExpression> for Term {
+    fn from_root(root: BooleanExpression) -> Self {
+        let root = match root.into_operand().map(Implication::into_operand) {
+            Ok(Ok(term)) => return term,
+            Ok(Err(implication)) => implication.into(),
+            Err(root) => root,
+        };
+
+        Factor::ExpressionInParens(Box::new(root)).into()
+    }
+}
+
+impl Nested<BooleanExpression> for Factor {
+    fn from_root(root: BooleanExpression) -> Self {
+        let root = match root.into_operand().map(Implication::into_operand) {
+            Ok(Ok(term)) => match term.into_operand() {
+                Ok(factor) => return factor,
+                Err(term) => Implication::from(term).into(),
+            },
+            Ok(Err(implication)) => implication.into(),
+            Err(root) => root,
+        };
+
+        Self::ExpressionInParens(Box::new(root))
+    }
+}
+
+impl ToNode for Factor {
+    fn to_node(&self) -> Node {
+        let variable = |identifier: &Identifier| Node::Variable {
+            name: identifier.name.clone(),
+        };
+
+        match self {
+            Self::Variable(identifier) => variable(identifier),
+            Self::NegatedVariable(identifier) => Node::Not {
+                operand: Box::new(variable(identifier)),
+            },
+            Self::Constant(constant) => Node::Literal {
+                value: Json::Bool(*constant),
+            },
+            Self::ExpressionInParens(expression) => expression.to_node(),
+            Self::Negation(factor) => Node::Not {
+                operand: Box::new(factor.to_node()),
             },
-            Self::NegatedVariable(string) => {
-                fetch_variable(string, variables_table, true)
+            Self::Comparison(lhs, operator, rhs) => {
+                Node::binary(operator, lhs.to_node(), rhs.to_node())
+            },
+            Self::Membership(item, set) => Node::In {
+                item: Box::new(item.to_node()),
+                set: set.iter().map(ToNode::to_node).collect(),
             },
         }
     }
 }
 
+impl FromNode for Factor {
+    fn from_node(node: &Node) -> Result<Self> {
+        let factor = match node {
+            Node::Literal {
+                value: Json::Bool(constant),
+            } => Self::Constant(*constant),
+            Node::Variable { name } => Self::Variable(json::name(name)?),
+            Node::Not { operand } => match Self::from_node(operand)? {
+                // Negated constants are folded, as the parser does.
+                Self::Constant(constant) => Self::Constant(!constant),
+                Self::Variable(identifier) => Self::NegatedVariable(identifier),
+                factor => Self::Negation(Box::new(factor)),
+            },
+            Node::Binary { operator, lhs, rhs } => {
+                match operator.parse::<ComparisonOperator>() {
+                    Ok(operator) => Self::Comparison(
+                        ArithmeticExpression::from_node(lhs)?,
+                        operator,
+                        ArithmeticExpression::from_node(rhs)?,
+                    ),
+                    Err(_)
+                        if node.is_operation::<TermOperator>()
+                            || node.is_operation::<ImplicationOperator>()
+                            || node.is_operation::<ExpressionOperator>() =>
+                    {
+                        let expression = BooleanExpression::from_node(node)?;
+
+                        Self::ExpressionInParens(Box::new(expression))
+                    },
+                    Err(_) => return Err(node.unexpected("a condition")),
+                }
+            },
+            Node::In { item, set } => Self::Membership(
+                ArithmeticExpression::from_node(item)?,
+                set.iter()
+                    .map(ArithmeticExpression::from_node)
+                    .collect::<Result<_>>()?,
+            ),
+            _ => return Err(node.unexpected("a condition")),
+        };
+
+        Ok(factor)
+    }
+}
+
+impl TypeCheck for Factor {
+    fn type_check(&self, types: &Types) -> Result<Option<Type>, TypeError> {
+        match self {
+            Self::Constant(_) => {},
+            Self::Variable(identifier) | Self::NegatedVariable(identifier) => {
+                match types.get(&identifier.name) {
+                    Some(Type::Bool) | None => {},
+                    Some(found) => {
+                        return Err(TypeError::new(
+                            format!("expected bool, found {}", found),
+                            Some(identifier.span),
+                        ))
+                    },
+                }
+            },
+            Self::ExpressionInParens(expression) => {
+                expression.type_check(types)?;
+            },
+            Self::Negation(factor) => {
+                factor.type_check(types)?;
+            },
+            Self::Comparison(lhs, operator, rhs) => {
+                if operator.is_equality() {
+                    unify(lhs, rhs, types)?;
+                } else {
+                    expect(lhs, Type::Number, types)?;
+                    expect(rhs, Type::Number, types)?;
+                }
+            },
+            Self::Membership(item, set) => {
+                item.type_check(types)?;
+                for element in set {
+                    unify(item, element, types)?;
+                }
+            },
+        }
+
+        Ok(Some(Type::Bool))
+    }
+
+    fn span(&self) -> Option<Span> {
+        match self {
+            Self::Variable(identifier) | Self::NegatedVariable(identifier) => {
+                Some(identifier.span)
+            },
+            _ => None,
+        }
+    }
+}
+
 impl Expression for Factor {
     /// Try convert the input into a number or variable name, or a
     /// parenthesized expression.
     ///
     /// # Example
     ///
-    /// ```
+    /// ```ignore
     /// use crate::expression::boolean::factor::Factor;
     ///
     /// let variable = Factor::parse(" !true ").unwrap().1;
     /// assert_eq!(variable, Factor::Constant(false));
     /// ```
     fn parse(input: &str) -> IResult<&str, Self> {
-        delimited(space0, alt((constant, variable)), space0)(input)
+        delimited(
+            space0,
+            alt((membership, comparison, constant, variable, expr, negation)),
+            space0,
+        )(input)
     }
 }
 
-fn constant(input: &str) -> IResult<&str, Factor> {
+fn expr(input: &str) -> IResult<&str, Factor> {
+    let expr = delimited(tag("("), BooleanExpression::parse, tag(")"));
+
+    map(expr, |expression| {
+        Factor::ExpressionInParens(Box::new(expression))
+    })(input)
+}
+
+fn negation(input: &str) -> IResult<&str, Factor> {
+    map(preceded(negation_sign, Factor::parse), Factor::negated)(input)
+}
+
+/// Parses `!`, as well as its `not` and `¬` aliases.
+fn negation_sign(input: &str) -> IResult<&str, &str> {
+    terminated(alt((token("!"), token("not"), token("¬"))), space0)(input)
+}
+
+fn comparison(input: &str) -> IResult<&str, Factor> {
+    let parser = tuple((
+        ArithmeticExpression::parse,
+        ComparisonOperator::parse,
+        ArithmeticExpression::parse,
+    ));
+
+    map(parser, |(lhs, operator, rhs)| {
+        Factor::Comparison(lhs, operator, rhs)
+    })(input)
+}
+
+fn membership(input: &str) -> IResult<&str, Factor> {
+    let separator = delimited(space0, char(','), space0);
+    let set = delimited(
+        pair(char('['), space0),
+        separated_list(separator, ArithmeticExpression::parse),
+        pair(space0, char(']')),
+    );
+
     map(
-        pair(opt(tag("!")), alt((tag("true"), tag("false")))),
-        |parsed| {
-            let constant = match parsed {
-                (Some(_), "true") | (None, "false") => false,
-                (Some(_), "false") | (None, "true") => true,
-                _ => unreachable!(), // Really is. It's either true or false.
-            };
-
-            Factor::Constant(constant)
-        },
+        tuple((ArithmeticExpression::parse, token("in"), space0, set)),
+        |(item, _, _, set)| Factor::Membership(item, set),
     )(input)
 }
 
+fn constant(input: &str) -> IResult<&str, Factor> {
+    let value =
+        alt((map(token("true"), |_| true), map(token("false"), |_| false)));
+
+    map(pair(opt(negation_sign), value), |(negated, value)| {
+        Factor::Constant(value != negated.is_some())
+    })(input)
+}
+
 fn variable(input: &str) -> IResult<&str, Factor> {
     map(
-        pair(opt(tag("!")), alpha1),
-        |parsed: (Option<&str>, &str)| match parsed {
-            (Some(_), negated) => Factor::NegatedVariable(negated.into()),
-            (None, variable) => Factor::Variable(variable.into()),
+        pair(opt(negation_sign), Identifier::parse),
+        |parsed: (Option<&str>, Identifier)| match parsed {
+            (Some(_), negated) => Factor::NegatedVariable(negated),
+            (None, variable) => Factor::Variable(variable),
         },
     )(input)
 }
@@ -99,6 +612,11 @@ mod tests {
         assert_eq!(Ok(("", Factor::Constant(false))), Factor::parse("!true"));
         assert_eq!(Ok(("", Factor::Constant(false))), Factor::parse("false"));
         assert_eq!(Ok(("", Factor::Constant(true))), Factor::parse("true"));
+        assert_eq!(
+            Ok(("", Factor::Constant(true))),
+            Factor::parse("NOT False")
+        );
+        assert_eq!(Ok(("", Factor::Constant(false))), Factor::parse("¬TRUE"));
     }
 
     #[test]
@@ -113,4 +631,101 @@ mod tests {
             Factor::parse("!VARNAME")
         );
     }
+
+    #[test]
+    fn test_parsing_negation() {
+        let expected = Factor::Negation(Box::new(Factor::NegatedVariable(
+            "VARNAME".into(),
+        )));
+
+        assert_eq!(Ok(("", expected)), Factor::parse("!!VARNAME"));
+        assert_eq!(
+            Ok(("", Factor::Constant(true))),
+            Factor::parse("!((false))")
+        );
+    }
+
+    #[test]
+    fn test_parsing_comparison() {
+        let factor = Factor::parse("D * 2 >= E").unwrap();
+
+        assert!(matches!(
+            factor,
+            (
+                "",
+                Factor::Comparison(_, ComparisonOperator::GreaterOrEqual, _)
+            )
+        ));
+    }
+
+    #[test]
+    fn test_text_comparison_reducibility() {
+        let mut context = Context::default();
+        context.variables.insert("country".into(), "DE".into());
+        context.variables.insert("home".into(), "DE".into());
+
+        let literal = Factor::parse(r#"country != "DE""#).unwrap().1;
+        let variable = Factor::parse("country == home").unwrap().1;
+
+        assert_eq!(false, literal.reduce(&context).unwrap());
+        assert_eq!(true, variable.reduce(&context).unwrap());
+    }
+
+    #[test]
+    fn test_membership_reducibility() {
+        let mut context = Context::default();
+        context.variables.insert("country".into(), "AT".into());
+
+        let factor = Factor::parse(r#"country in ["DE", "AT", "CH"]"#).unwrap();
+        let empty = Factor::parse("country in []").unwrap().1;
+
+        assert_eq!("", factor.0);
+        assert_eq!(true, factor.1.reduce(&context).unwrap());
+        assert_eq!(false, empty.reduce(&context).unwrap());
+    }
+
+    #[test]
+    fn test_comparison_reducibility() {
+        let mut context = Context::default();
+        context.variables.insert("D".into(), 150.0.into());
+        context.variables.insert("E".into(), 100.into());
+
+        let factor = Factor::parse("D > E").unwrap().1;
+
+        assert_eq!(true, factor.reduce(&context).unwrap());
+    }
+
+    #[test]
+    fn test_unknown_reducibility() {
+        let mut context = Context::default();
+        context.variables.insert("A".into(), true.into());
+
+        let reduce = |source| {
+            Factor::parse(source).unwrap().1.reduce_partially(&context)
+        };
+
+        assert_eq!(Truth::Unknown, reduce("!B").unwrap());
+        assert_eq!(Truth::Unknown, reduce("D > 100").unwrap());
+        assert_eq!(Truth::False, reduce("!A").unwrap());
+        assert!(reduce("A > 1").is_err());
+    }
+
+    #[test]
+    fn test_type_checking() {
+        let mut types = Types::new();
+        types.insert("D".into(), Type::Number);
+        types.insert("country".into(), Type::String);
+
+        let source = r#"country in ["DE", D]"#;
+        let factor = Factor::parse(source).unwrap().1;
+        let error = factor.type_check(&types).unwrap_err();
+
+        assert_eq!("mismatched types string and number", error.message);
+        assert_eq!(18..19, error.span.resolve(source));
+
+        let source = "!D";
+        let error = Factor::parse(source).unwrap().1.type_check(&types);
+
+        assert_eq!(1..2, error.unwrap_err().span.resolve(source));
+    }
 }
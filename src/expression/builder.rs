@@ -0,0 +1,442 @@
+/// This module implements a typed API to build rules in code, rather than
+/// format them as strings:
+///
+/// ```ignore
+/// let rule = var("A").and(not(var("C"))).then_label("M");
+/// let formula = label("M").formula(var("D") + var("D") * var("E") / 10.0);
+/// ```
+///
+/// The rules are the very trees the parser produces for the same rules
+/// written down, with parentheses wherever the text would need them.
+///
+/// Names are checked as they are given, the way the parser checks them.
+/// They are mostly written in code, so an invalid one, such as `if` or
+/// `a b`, is a bug to fix rather than an error to handle, and panics.
+use std::ops::{Add, Div, Mul, Sub};
+
+use super::arithmetic::{self, Formula, Matcher, Output, RESERVED_OUTPUTS};
+use super::boolean::{self, ComparisonOperator, Implication};
+use super::common::{Identifier, Nested, Span};
+use super::json;
+use super::number::Number;
+use super::rule::Rule;
+
+type ArithmeticExpression = arithmetic::Expression;
+type BooleanExpression = boolean::Expression;
+
+/// A variable, which may take part in either a condition or a formula.
+pub struct Variable(Identifier);
+
+/// The labels a formula applies to, possibly guarded by a condition.
+pub struct Labels {
+    matcher: Matcher,
+    guard: Option<BooleanExpression>,
+}
+
+pub fn var(name: &str) -> Variable {
+    Variable(checked(name))
+}
+
+/// A string literal, such as `"DE"`.
+pub fn text(text: &str) -> ArithmeticExpression {
+    arithmetic::Factor::Text(text.into(), Span::default()).into()
+}
+
+/// `!condition`.
+pub fn not(condition: impl Condition) -> BooleanExpression {
+    let operand =
+        boolean::Factor::ExpressionInParens(Box::new(condition.into()));
+
+    operand.negated().into()
+}
+
+/// `if(condition, then, otherwise)`.
+pub fn if_else(
+    condition: impl Condition,
+    then: impl Quantity,
+    otherwise: impl Quantity,
+) -> ArithmeticExpression {
+    arithmetic::Factor::Conditional(
+        Box::new(condition.into()),
+        Box::new(then.into()),
+        Box::new(otherwise.into()),
+    )
+    .into()
+}
+
+/// `H = label => ...`.
+pub fn label(label: &str) -> Labels {
+    Labels {
+        matcher: Matcher::Labels(vec![checked(label).name]),
+        guard: None,
+    }
+}
+
+/// `H = * => ...`.
+pub fn any_label() -> Labels {
+    Labels {
+        matcher: Matcher::Wildcard,
+        guard: None,
+    }
+}
+
+/// `const name = value`.
+pub fn constant(name: &str, value: impl Quantity) -> Rule {
+    Rule::Constant(checked(name).name, value.into())
+}
+
+/// `else => H = label`.
+pub fn default_label(label: &str) -> Rule {
+    Rule::Default(checked(label).name)
+}
+
+impl Labels {
+    /// Adds an alternative label, as in `H = M | P => ...`. A wildcard
+    /// has no alternatives.
+    pub fn or(mut self, label: &str) -> Self {
+        match &mut self.matcher {
+            Matcher::Labels(labels) => labels.push(checked(label).name),
+            Matcher::Wildcard => {
+                panic!("A wildcard can't have alternatives, such as {}", label)
+            },
+        }
+
+        self
+    }
+
+    /// Guards the formula, as in `H = P && D > 100 => ...`.
+    pub fn when(mut self, guard: impl Condition) -> Self {
+        self.guard = Some(guard.into());
+
+        self
+    }
+
+    /// `... => K = formula`.
+    pub fn formula(self, formula: impl Quantity) -> Rule {
+        self.outputs(vec![("K", formula.into())])
+    }
+
+    /// `... => net = D, tax = net * 0.19`.
+    pub fn outputs<'a>(
+        self,
+        outputs: impl IntoIterator<Item = (&'a str, ArithmeticExpression)>,
+    ) -> Rule {
+        let outputs = outputs
+            .into_iter()
+            .map(|(name, expression)| Output {
+                name: output_name(name),
+                span: Span::default(),
+                precision: None,
+                expression,
+            })
+            .collect();
+        let formula = Formula {
+            matcher: self.matcher,
+            guard: self.guard,
+            outputs,
+        };
+
+        if let Some((_, output)) = formula.duplicate_output() {
+            panic!("{} is already assigned", output.name);
+        }
+
+        Rule::Arithmetic(formula)
+    }
+}
+
+/// Anything a condition can be built of.
+pub trait Condition: Into<BooleanExpression> {
+    fn and(self, rhs: impl Condition) -> BooleanExpression {
+        connect(self, boolean::TermOperator::And, rhs)
+    }
+
+    fn or(self, rhs: impl Condition) -> BooleanExpression {
+        connect(self, boolean::TermOperator::Or, rhs)
+    }
+
+    fn xor(self, rhs: impl Condition) -> BooleanExpression {
+        connect(self, boolean::TermOperator::Xor, rhs)
+    }
+
+    fn implies(self, rhs: impl Condition) -> BooleanExpression {
+        let mut implication = Implication::from_root(self.into());
+        let operand = boolean::Term::from_root(rhs.into());
+        implication
+            .tail
+            .push((boolean::ImplicationOperator::Implies, operand));
+
+        implication.into()
+    }
+
+    fn iff(self, rhs: impl Condition) -> BooleanExpression {
+        let mut expression = self.into();
+        let operand = Implication::from_root(rhs.into());
+        expression
+            .tail
+            .push((boolean::ExpressionOperator::Iff, operand));
+
+        expression
+    }
+
+    /// `condition => H = label`.
+    fn then_label(self, label: &str) -> Rule {
+        Rule::Boolean(self.into(), checked(label).name)
+    }
+}
+
+/// Anything a formula can be built of. The operators are overloaded, so
+/// that `var("D") * 2` is `D * 2`.
+pub trait Quantity: Into<ArithmeticExpression> {
+    fn equals(self, rhs: impl Quantity) -> BooleanExpression {
+        compare(self, ComparisonOperator::Equal, rhs)
+    }
+
+    fn differs_from(self, rhs: impl Quantity) -> BooleanExpression {
+        compare(self, ComparisonOperator::NotEqual, rhs)
+    }
+
+    fn less_than(self, rhs: impl Quantity) -> BooleanExpression {
+        compare(self, ComparisonOperator::Less, rhs)
+    }
+
+    fn at_most(self, rhs: impl Quantity) -> BooleanExpression {
+        compare(self, ComparisonOperator::LessOrEqual, rhs)
+    }
+
+    fn greater_than(self, rhs: impl Quantity) -> BooleanExpression {
+        compare(self, ComparisonOperator::Greater, rhs)
+    }
+
+    fn at_least(self, rhs: impl Quantity) -> BooleanExpression {
+        compare(self, ComparisonOperator::GreaterOrEqual, rhs)
+    }
+
+    /// `self in [set]`.
+    fn one_of<T: Quantity>(
+        self,
+        set: impl IntoIterator<Item = T>,
+    ) -> BooleanExpression {
+        let set = set.into_iter().map(Into::into).collect();
+
+        boolean::Factor::Membership(self.into(), set).into()
+    }
+}
+
+impl Condition for BooleanExpression {}
+impl Condition for Variable {}
+impl Condition for bool {}
+
+impl Quantity for ArithmeticExpression {}
+impl Quantity for Variable {}
+impl Quantity for f64 {}
+impl Quantity for i64 {}
+impl Quantity for i32 {}
+
+impl From<Variable> for BooleanExpression {
+    fn from(variable: Variable) -> Self {
+        boolean::Factor::Variable(variable.0).into()
+    }
+}
+
+impl From<bool> for BooleanExpression {
+    fn from(constant: bool) -> Self {
+        boolean::Factor::Constant(constant).into()
+    }
+}
+
+impl From<boolean::Factor> for BooleanExpression {
+    fn from(factor: boolean::Factor) -> Self {
+        Implication::from(boolean::Term::from(factor)).into()
+    }
+}
+
+impl From<Variable> for ArithmeticExpression {
+    fn from(variable: Variable) -> Self {
+        arithmetic::Factor::Variable(variable.0).into()
+    }
+}
+
+impl From<f64> for ArithmeticExpression {
+    fn from(constant: f64) -> Self {
+        arithmetic::Factor::Constant(Number::Float(constant).into()).into()
+    }
+}
+
+impl From<i64> for ArithmeticExpression {
+    fn from(constant: i64) -> Self {
+        arithmetic::Factor::Constant(Number::Int(constant).into()).into()
+    }
+}
+
+impl From<i32> for ArithmeticExpression {
+    fn from(constant: i32) -> Self {
+        i64::from(constant).into()
+    }
+}
+
+impl From<arithmetic::Factor> for ArithmeticExpression {
+    fn from(factor: arithmetic::Factor) -> Self {
+        arithmetic::Term::from(factor).into()
+    }
+}
+
+macro_rules! overload {
+    ($trait:ident, $method:ident, $combine:ident, $operator:expr) => {
+        impl<R: Quantity> $trait<R> for ArithmeticExpression {
+            type Output = ArithmeticExpression;
+
+            fn $method(self, rhs: R) -> ArithmeticExpression {
+                $combine(self, $operator, rhs)
+            }
+        }
+
+        impl<R: Quantity> $trait<R> for Variable {
+            type Output = ArithmeticExpression;
+
+            fn $method(self, rhs: R) -> ArithmeticExpression {
+                $combine(self, $operator, rhs)
+            }
+        }
+    };
+}
+
+overload!(Add, add, sum, arithmetic::ExpressionOperator::Add);
+overload!(Sub, sub, sum, arithmetic::ExpressionOperator::Subtract);
+overload!(Mul, mul, product, arithmetic::TermOperator::Multiply);
+overload!(Div, div, product, arithmetic::TermOperator::Divide);
+
+/// Checks the name, see `json::name`.
+fn checked(name: &str) -> Identifier {
+    json::name(name).unwrap_or_else(|error| panic!("{}", error))
+}
+
+fn output_name(name: &str) -> String {
+    if RESERVED_OUTPUTS.contains(&name) {
+        panic!("An output can't be named {}", name);
+    }
+
+    checked(name).name
+}
+
+fn sum(
+    lhs: impl Quantity,
+    operator: arithmetic::ExpressionOperator,
+    rhs: impl Quantity,
+) -> ArithmeticExpression {
+    let mut expression = lhs.into();
+    expression
+        .tail
+        .push((operator, arithmetic::Term::from_root(rhs.into())));
+
+    expression
+}
+
+fn product(
+    lhs: impl Quantity,
+    operator: arithmetic::TermOperator,
+    rhs: impl Quantity,
+) -> ArithmeticExpression {
+    let mut term = arithmetic::Term::from_root(lhs.into());
+    term.tail
+        .push((operator, arithmetic::Factor::from_root(rhs.into())));
+
+    term.into()
+}
+
+fn connect(
+    lhs: impl Condition,
+    operator: boolean::TermOperator,
+    rhs: impl Condition,
+) -> BooleanExpression {
+    let mut term = boolean::Term::from_root(lhs.into());
+    term.tail
+        .push((operator, boolean::Factor::from_root(rhs.into())));
+
+    Implication::from(term).into()
+}
+
+fn compare(
+    lhs: impl Quantity,
+    operator: ComparisonOperator,
+    rhs: impl Quantity,
+) -> BooleanExpression {
+    boolean::Factor::Comparison(lhs.into(), operator, rhs.into()).into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::expression::common::Expression;
+
+    fn arithmetic(source: &str) -> ArithmeticExpression {
+        ArithmeticExpression::parse(source).unwrap().1
+    }
+
+    fn boolean(source: &str) -> BooleanExpression {
+        BooleanExpression::parse(source).unwrap().1
+    }
+
+    #[test]
+    fn test_rules() {
+        let rule = var("A").and(not(var("C"))).then_label("M");
+
+        assert_eq!(Rule::parse("A && !C => H = M").unwrap(), rule);
+
+        let rule = label("M").formula(var("D") + var("D") * var("E") / 10.0);
+        let expected = Rule::parse("H = M => K = D + D * E / 10.0").unwrap();
+
+        assert_eq!(expected, rule);
+
+        let rule = label("M")
+            .or("P")
+            .when(var("D").greater_than(100))
+            .outputs(vec![("net", var("D") * 2), ("tax", var("net") * 0.19)]);
+        let source = "H = M | P && D > 100 => net = D * 2, tax = net * 0.19";
+
+        assert_eq!(Rule::parse(source).unwrap(), rule);
+    }
+
+    #[test]
+    #[should_panic(expected = "Invalid name if")]
+    fn test_keyword_names() {
+        var("if");
+    }
+
+    #[test]
+    #[should_panic(expected = "Invalid name a b")]
+    fn test_invalid_names() {
+        label("M").outputs(vec![("a b", var("D") * 2)]);
+    }
+
+    #[test]
+    #[should_panic(expected = "A wildcard can't have alternatives, such as M")]
+    fn test_wildcard_alternatives() {
+        any_label().or("M");
+    }
+
+    #[test]
+    #[should_panic(expected = "net is already assigned")]
+    fn test_duplicate_outputs() {
+        label("M").outputs(vec![("net", var("D") + 1), ("net", var("D") * 2)]);
+    }
+
+    #[test]
+    fn test_parentheses() {
+        assert_eq!(arithmetic("(D + 1) * E"), (var("D") + 1) * var("E"));
+        assert_eq!(arithmetic("D - (E - F)"), var("D") - (var("E") - var("F")));
+        assert_eq!(
+            arithmetic("if(A, D, 0) / (D * E)"),
+            if_else(var("A"), var("D"), 0) / (var("D") * var("E"))
+        );
+        assert_eq!(
+            boolean("A || B -> (C <-> A)"),
+            var("A").or(var("B")).implies(var("C").iff(var("A")))
+        );
+        assert_eq!(
+            boolean(r#"!(A && B) ^^ country in ["DE", "AT"]"#),
+            not(var("A").and(var("B")))
+                .xor(var("country").one_of(vec![text("DE"), text("AT")]))
+        );
+    }
+}
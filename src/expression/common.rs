@@ -1,13 +1,23 @@
 use std::collections::HashMap;
+use std::fmt;
+use std::ops::Range;
 use std::str::FromStr;
 
-use anyhow::Result;
+use anyhow::{Context as _, Result};
 
-use nom::combinator::{map, map_res};
+use nom::branch::alt;
+use nom::bytes::complete::{tag, tag_no_case};
+use nom::character::complete::{alpha1, alphanumeric1, char};
+use nom::combinator::{map, map_res, recognize, verify};
+use nom::error::ErrorKind;
 use nom::multi::many0;
 use nom::sequence::pair;
 use nom::IResult;
 
+use super::number::{Arithmetic, FloatPolicy};
+use super::rational::Rational;
+use super::value::Value;
+
 pub trait Expression: Sized {
     fn parse(input: &str) -> IResult<&str, Self>;
 
@@ -18,7 +28,7 @@ pub trait Expression: Sized {
     }
 }
 
-#[derive(PartialEq, Debug)]
+#[derive(Clone, PartialEq, Debug)]
 pub struct ExpressionWithOperator<T: Expression, O: Operator> {
     pub head: T,
     pub tail: Vec<(O, T)>,
@@ -26,17 +36,310 @@ pub struct ExpressionWithOperator<T: Expression, O: Operator> {
 
 impl<T, O> Expression for ExpressionWithOperator<T, O>
 where
-    T: Expression,
-    O: Operator,
+    T: Expression + Parenthesized + Nested<T::Root>,
+    O: Operator + Precedence,
+    Self: Nested<T::Root>,
 {
+    /// Parentheses the precedence doesn't need are left out of the tree,
+    /// just as they are left out when printing, so that `(D * 2) + 1` is
+    /// parsed into the same tree as `D * 2 + 1`.
     fn parse(input: &str) -> IResult<&str, Self> {
-        map(Self::parse_with_operator::<T, O>, |(head, tail)| Self {
-            head,
-            tail,
+        map(Self::parse_with_operator::<T, O>, |(head, tail)| {
+            let mut chain =
+                unparenthesized(head, O::PRECEDENCE).unwrap_or_else(Self::from);
+
+            for (operator, item) in tail {
+                let item = unparenthesized(item, O::PRECEDENCE + 1)
+                    .unwrap_or_else(|item| item);
+
+                chain.tail.push((operator, item));
+            }
+
+            chain
         })(input)
     }
 }
 
+impl<T, O> ExpressionWithOperator<T, O>
+where
+    T: Expression,
+    O: Operator,
+{
+    /// The only operand of the chain, unless it has operators.
+    pub fn into_operand(self) -> Result<T, Self> {
+        if self.tail.is_empty() {
+            Ok(self.head)
+        } else {
+            Err(self)
+        }
+    }
+}
+
+/// Operands which may be nothing but an expression in parentheses, such as
+/// `(D + 1)`.
+pub trait Parenthesized: Sized {
+    /// Expressions within the parentheses.
+    type Root: Canonical;
+
+    /// The expression within the parentheses, if the operand is nothing
+    /// else.
+    fn parenthesized(&self) -> Option<&Self::Root>;
+
+    fn into_parenthesized(self) -> Result<Self::Root, Self>;
+}
+
+/// Parts of the expression tree any expression may be turned into, put in
+/// parentheses unless it binds tightly enough.
+pub trait Nested<R>: Sized {
+    fn from_root(root: R) -> Self;
+}
+
+/// The operand without the parentheses around it, unless the position
+/// requires them.
+pub fn unparenthesized<T, N>(operand: T, precedence: u8) -> Result<N, T>
+where
+    T: Parenthesized,
+    N: Nested<T::Root>,
+{
+    match operand.parenthesized().map(Canonical::precedence) {
+        Some(None) => {},
+        Some(Some(inner)) if inner >= precedence => {},
+        _ => return Err(operand),
+    }
+
+    operand.into_parenthesized().map(N::from_root)
+}
+
+impl<T, O> Parenthesized for ExpressionWithOperator<T, O>
+where
+    T: Expression + Parenthesized,
+    O: Operator,
+{
+    type Root = T::Root;
+
+    fn parenthesized(&self) -> Option<&Self::Root> {
+        if self.tail.is_empty() {
+            self.head.parenthesized()
+        } else {
+            None
+        }
+    }
+
+    fn into_parenthesized(self) -> Result<Self::Root, Self> {
+        self.into_operand()?
+            .into_parenthesized()
+            .map_err(Self::from)
+    }
+}
+
+/// Where a sub-expression is printed, which tells whether it needs
+/// parentheses.
+#[derive(Clone, Copy, Default, Debug)]
+pub struct Position {
+    /// Expressions with looser operators must be parenthesized.
+    pub precedence: u8,
+    /// Whether anything follows, which greedy expressions, such as let
+    /// bindings, would swallow.
+    pub followed: bool,
+}
+
+impl Position {
+    /// The position of an operand of a prefix operator, such as `!`.
+    pub fn operand(followed: bool) -> Self {
+        Self {
+            precedence: u8::MAX,
+            followed,
+        }
+    }
+}
+
+/// Expressions printed in the canonical form, which has single spaces
+/// around binary operators, canonical spelling of operators and only the
+/// parentheses the precedence requires. The parser leaves redundant
+/// parentheses out of the tree, so printing a parsed tree and parsing it
+/// back yields an equal tree.
+pub trait Canonical {
+    /// The precedence of the loosest operator outside of parentheses, if
+    /// any.
+    fn precedence(&self) -> Option<u8>;
+
+    fn write(
+        &self,
+        formatter: &mut fmt::Formatter,
+        position: Position,
+    ) -> fmt::Result;
+
+    /// Writes the expression, parenthesized if it binds looser than the
+    /// position requires.
+    fn write_within(
+        &self,
+        formatter: &mut fmt::Formatter,
+        position: Position,
+    ) -> fmt::Result {
+        match self.precedence() {
+            Some(precedence) if precedence < position.precedence => {
+                formatter.write_str("(")?;
+                self.write(formatter, Position::default())?;
+                formatter.write_str(")")
+            },
+            _ => self.write(formatter, position),
+        }
+    }
+}
+
+/// Operators of the same precedence, the higher the tighter they bind.
+pub trait Precedence {
+    const PRECEDENCE: u8;
+}
+
+impl<T, O> Canonical for ExpressionWithOperator<T, O>
+where
+    T: Expression + Canonical,
+    O: Operator + Precedence + fmt::Display,
+{
+    fn precedence(&self) -> Option<u8> {
+        if self.tail.is_empty() {
+            self.head.precedence()
+        } else {
+            Some(O::PRECEDENCE)
+        }
+    }
+
+    fn write(
+        &self,
+        formatter: &mut fmt::Formatter,
+        position: Position,
+    ) -> fmt::Result {
+        self.write_prefix(formatter, self.tail.len(), position)
+    }
+}
+
+impl<T, O> fmt::Display for ExpressionWithOperator<T, O>
+where
+    T: Expression + Canonical,
+    O: Operator + Precedence + fmt::Display,
+{
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        self.write(formatter, Position::default())
+    }
+}
+
+impl<T, O> ExpressionWithOperator<T, O>
+where
+    T: Expression + Canonical,
+    O: Operator + Precedence + fmt::Display,
+{
+    /// Writes the head and the first `count` operations of the tail. Left
+    /// operands may have operators of the same precedence, whereas right
+    /// ones need parentheses for that, since operators are applied left to
+    /// right.
+    fn write_prefix(
+        &self,
+        formatter: &mut fmt::Formatter,
+        count: usize,
+        position: Position,
+    ) -> fmt::Result {
+        if count == 0 {
+            return self.head.write(formatter, position);
+        }
+
+        let head = Position {
+            precedence: O::PRECEDENCE,
+            followed: true,
+        };
+        self.head.write_within(formatter, head)?;
+
+        for (index, (operator, item)) in self.tail[..count].iter().enumerate() {
+            let position = Position {
+                precedence: O::PRECEDENCE + 1,
+                followed: index + 1 < count || position.followed,
+            };
+
+            write!(formatter, " {} ", operator)?;
+            item.write_within(formatter, position)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// The head and the first operations of an expression, printed the way
+/// they are printed within the whole expression.
+struct Prefix<'a, T: Expression, O: Operator>(
+    &'a ExpressionWithOperator<T, O>,
+    usize,
+);
+
+impl<T, O> fmt::Display for Prefix<'_, T, O>
+where
+    T: Expression + Canonical,
+    O: Operator + Precedence + fmt::Display,
+{
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        self.0.write_prefix(formatter, self.1, Position::default())
+    }
+}
+
+impl<T, O> ExpressionWithOperator<T, O>
+where
+    T: Expression + Canonical + Variables,
+    O: Operator + Precedence + fmt::Display,
+{
+    /// Points the error of the operation at `index` of the tail to the
+    /// sub-expression it happened in, listing values of the variables the
+    /// sub-expression refers to.
+    pub fn explain(
+        &self,
+        error: anyhow::Error,
+        index: usize,
+        context: &Context,
+    ) -> anyhow::Error {
+        let source = Prefix(self, index + 1);
+        let mut names = vec![];
+        self.head.variables(&mut names);
+
+        for (_, item) in &self.tail[..=index] {
+            item.variables(&mut names);
+        }
+
+        let mut values: Vec<String> = vec![];
+        for name in names {
+            if let Ok(value) = context.get(name) {
+                let binding = format!("{} = {}", name, value);
+
+                if !values.contains(&binding) {
+                    values.push(binding);
+                }
+            }
+        }
+
+        if values.is_empty() {
+            anyhow::anyhow!("{} in {}", error, source)
+        } else {
+            anyhow::anyhow!(
+                "{} in {} where {}",
+                error,
+                source,
+                values.join(", ")
+            )
+        }
+    }
+}
+
+impl<T, O> Variables for ExpressionWithOperator<T, O>
+where
+    T: Expression + Variables,
+    O: Operator,
+{
+    fn variables<'a>(&'a self, names: &mut Vec<&'a str>) {
+        self.head.variables(names);
+
+        for (_, item) in &self.tail {
+            item.variables(names);
+        }
+    }
+}
+
 pub trait Operator: FromStr {
     fn parser() -> Box<dyn Fn(&str) -> IResult<&str, &str>>;
 
@@ -45,8 +348,121 @@ pub trait Operator: FromStr {
     }
 }
 
+/// Variables an expression is reduced against.
+#[derive(Clone, Default, Debug)]
+pub struct Context {
+    pub variables: HashMap<String, Value>,
+    /// Named constants of the rule set, such as `const RATE_P = 25.5`.
+    pub constants: HashMap<String, Value>,
+    /// Let bindings reduced without any rounding, which don't fit into
+    /// values.
+    pub exact_bindings: HashMap<String, Rational>,
+    /// How constants and variables are represented as numbers.
+    pub arithmetic: Arithmetic,
+    /// What to do when floats run into infinities.
+    pub floats: FloatPolicy,
+}
+
+impl Context {
+    /// Looks the name up among the variables first, so that a request may
+    /// override constants of the rule set.
+    pub fn get(&self, name: &str) -> Result<&Value> {
+        self.variables
+            .get(name)
+            .or_else(|| self.constants.get(name))
+            .with_context(|| format!("Variable {} is undefined", name))
+    }
+
+    /// Binds the name for the exact evaluation. Conditions are evaluated
+    /// the usual way, so they see the nearest float instead.
+    pub fn bind_exact(&mut self, name: &str, value: Rational) {
+        self.variables.insert(name.into(), value.to_f64().into());
+        self.exact_bindings.insert(name.into(), value);
+    }
+}
+
+/// Location of a parsed piece of the source.
+///
+/// Parsers only see the rest of the input, so the location is measured from
+/// the end of the source and is resolved into byte offsets once the source
+/// is known.
+///
+/// Spans never take part in comparisons, so that trees parsed from
+/// differently formatted sources are equal.
+#[derive(Clone, Copy, Default, Debug)]
+pub struct Span {
+    from_end: usize,
+    length: usize,
+}
+
+impl Span {
+    pub fn resolve(&self, source: &str) -> Range<usize> {
+        let start = source.len().saturating_sub(self.from_end);
+
+        start..(start + self.length).min(source.len())
+    }
+}
+
+impl PartialEq for Span {
+    fn eq(&self, _: &Self) -> bool {
+        true
+    }
+}
+
+/// A variable name along with its location in the source.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Identifier {
+    pub name: String,
+    pub span: Span,
+}
+
+impl From<&str> for Identifier {
+    fn from(name: &str) -> Self {
+        Self {
+            name: name.into(),
+            span: Span::default(),
+        }
+    }
+}
+
+impl fmt::Display for Identifier {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str(&self.name)
+    }
+}
+
+impl Expression for Identifier {
+    fn parse(input: &str) -> IResult<&str, Self> {
+        map(spanned(identifier), |(name, span)| Self {
+            name: name.into(),
+            span,
+        })(input)
+    }
+}
+
+/// Runs the parser and records the span of whatever it consumed.
+pub fn spanned<'a, O>(
+    parser: impl Fn(&'a str) -> IResult<&'a str, O>,
+) -> impl Fn(&'a str) -> IResult<&'a str, (O, Span)> {
+    move |input: &'a str| {
+        let (rest, parsed) = parser(input)?;
+        let span = Span {
+            from_end: input.len(),
+            length: input.len() - rest.len(),
+        };
+
+        Ok((rest, (parsed, span)))
+    }
+}
+
 pub trait Reducible<N> {
-    fn reduce(&self, variables_table: &HashMap<String, N>) -> Result<N>;
+    fn reduce(&self, context: &Context) -> Result<N>;
+}
+
+/// Expressions which refer to variables.
+pub trait Variables {
+    /// Collects names of the variables, in the order of appearance.
+    fn variables<'a>(&'a self, names: &mut Vec<&'a str>);
 }
 
 fn variable_with_operator<T, S>(input: &str) -> IResult<&str, (S, T)>
@@ -57,18 +473,82 @@ where
     pair(S::parse, T::parse)(input)
 }
 
+/// Parses a single token, such as an operator or a keyword, ignoring the
+/// case. Word-form tokens, such as `xor`, must not be immediately followed
+/// by a character which may continue an identifier, so that `xored` or
+/// `or_x` are not mistaken for the operator followed by another identifier.
+pub fn token<'a>(
+    token: &'static str,
+) -> impl Fn(&'a str) -> IResult<&'a str, &'a str> {
+    move |input: &'a str| {
+        let (rest, matched) = tag_no_case(token)(input)?;
+        let is_word = token.chars().all(char::is_alphabetic);
+
+        match rest.chars().next() {
+            Some(next) if is_word && continues_identifier(next) => {
+                Err(nom::Err::Error((input, ErrorKind::Tag)))
+            },
+            _ => Ok((rest, matched)),
+        }
+    }
+}
+
+fn continues_identifier(character: char) -> bool {
+    character.is_alphanumeric() || character == '_' || character == '.'
+}
+
+/// Words reserved by the grammar, which can't be used as identifiers.
+const KEYWORDS: [&str; 10] = [
+    "and", "or", "xor", "not", "true", "false", "if", "in", "let", "else",
+];
+
+/// Parses an identifier, that is a variable name or a label. Identifiers
+/// match `[A-Za-z_][A-Za-z0-9_]*`, and may be joined with dots into paths,
+/// such as `customer.age`.
+pub fn identifier(input: &str) -> IResult<&str, &str> {
+    fn segment(input: &str) -> IResult<&str, &str> {
+        let tail = many0(alt((alphanumeric1, tag("_"))));
+
+        recognize(pair(alt((tag("_"), alpha1)), tail))(input)
+    }
+
+    let path = recognize(pair(segment, many0(pair(char('.'), segment))));
+
+    verify(path, |parsed: &str| {
+        !KEYWORDS
+            .iter()
+            .any(|keyword| parsed.eq_ignore_ascii_case(keyword))
+    })(input)
+}
+
 /// The basic idea of this macro is to generate the Expression type given a
 /// set of operations.
 ///
+/// Every operation lists the tokens it is spelled with, the first of which
+/// is the canonical one, and how it applies to a pair of already reduced
+/// operands. The operation may also refer to the evaluation context:
+///
+/// ```ignore
+/// expression!(Term<bool, Factor> | TermOperator(Bool):
+///     And("&&") => |lhs, rhs| Ok(lhs && rhs),
+///     Xor("^^", "xor") => |lhs, rhs, context| Ok(lhs ^ rhs));
+/// ```
+///
+/// Operations of a single expression type share the precedence and are
+/// applied left to right. Errors of operations point to the sub-expression
+/// they happened in.
+///
 /// The expression can then be parsed or evaluated (aka reduced) using
 /// `Expression` & `Reducible` traits correspondingly.
 #[macro_export]
 macro_rules! expression {
-    ($expression_type:ident<$type:ty, $consists_of:ty> | $name:ident: $($variant:ident => $op:tt),*) => {
+    ($expression_type:ident<$type:ty, $consists_of:ty> | $name:ident($operand:ident):
+     $($variant:ident($canonical:literal $(, $alias:literal)*) =>
+       |$lhs:ident, $rhs:ident $(, $context:ident)?| $apply:expr),*) => {
         pub type $expression_type =
-            crate::expression::common::ExpressionWithOperator<$consists_of, $name>;
+            $crate::expression::common::ExpressionWithOperator<$consists_of, $name>;
 
-        #[derive(PartialEq, Debug)]
+        #[derive(Clone, PartialEq, Debug)]
         pub enum $name {
             $( $variant, )*
         }
@@ -77,39 +557,324 @@ macro_rules! expression {
             type Err = anyhow::Error;
 
             fn from_str(input: &str) -> Result<Self, Self::Err> {
-                match input {
-                    $( stringify!($op) => Ok($name::$variant), )*
+                match input.to_lowercase().as_str() {
+                    $( $canonical $(| $alias)* => Ok($name::$variant), )*
                     _ => anyhow::bail!("Operator {} is not recognized", input)
                 }
             }
         }
 
-        impl crate::expression::common::Operator for $name {
+        impl std::fmt::Display for $name {
+            fn fmt(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+                match self {
+                    $( $name::$variant => formatter.write_str($canonical), )*
+                }
+            }
+        }
+
+        impl $crate::expression::common::Operator for $name {
             fn parser() -> Box<dyn Fn(&str) -> nom::IResult<&str, &str>> {
-                use nom::bytes::complete::tag;
-                use nom::branch::alt;
+                use $crate::expression::common::token;
 
                 Box::new(|input: &str| {
-                    alt(($( tag(stringify!($op)), )*))(input)
+                    $(
+                        for token_str in &[$canonical $(, $alias)*] {
+                            if let Ok(parsed) = token(token_str)(input) {
+                                return Ok(parsed);
+                            }
+                        }
+                    )*
+
+                    Err(nom::Err::Error((input, nom::error::ErrorKind::Tag)))
                 })
             }
         }
 
-        impl crate::expression::common::Reducible<$type> for $expression_type {
-            fn reduce(&self, variables_table: &std::collections::HashMap<String, $type>)
+        impl $crate::expression::common::Reducible<$type> for $expression_type {
+            fn reduce(&self, context: &$crate::expression::common::Context)
                       -> anyhow::Result<$type> {
-                let init = self.head.reduce(variables_table)?;
+                let init: $type = self.head.reduce(context)?;
+                let mut operations = self.tail.iter().enumerate();
 
-                self.tail.iter().try_fold(init, |acc, (operator, item)| {
-                    match operator {
+                operations.try_fold(init, |acc, (index, (operator, item))| {
+                    let rhs: $type = item.reduce(context)?;
+                    let result: anyhow::Result<$type> = match operator {
                         $( $name::$variant => {
-                            item.reduce(variables_table)
-                                .map(|result| acc $op result)
+                            fn apply(
+                                $lhs: $type,
+                                $rhs: $type,
+                                _context: &$crate::expression::common::Context,
+                            ) -> anyhow::Result<$type> {
+                                $( let $context = _context; )?
+
+                                $apply
+                            }
+
+                            apply(acc, rhs, context)
                         }, )*
-                    }
+                    };
+
+                    result.map_err(|error| self.explain(error, index, context))
                 })
             }
         }
 
+        impl $crate::expression::typing::TypeCheck for $expression_type {
+            fn type_check(&self, types: &$crate::expression::typing::Types)
+                -> Result<
+                    Option<$crate::expression::typing::Type>,
+                    $crate::expression::typing::TypeError,
+                > {
+                use $crate::expression::typing::{expect, Type};
+
+                // A lone operand is not constrained by operators.
+                if self.tail.is_empty() {
+                    return self.head.type_check(types);
+                }
+
+                expect(&self.head, Type::$operand, types)?;
+                for (_, item) in &self.tail {
+                    expect(item, Type::$operand, types)?;
+                }
+
+                Ok(Some(Type::$operand))
+            }
+
+            fn span(&self) -> Option<$crate::expression::common::Span> {
+                if self.tail.is_empty() {
+                    self.head.span()
+                } else {
+                    None
+                }
+            }
+        }
+
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_identifier_parsing() {
+        assert_eq!(Ok(("", "rate_2024")), identifier("rate_2024"));
+        assert_eq!(Ok(("", "_is_vip")), identifier("_is_vip"));
+        assert_eq!(
+            Ok((" > 1", "customer.age")),
+            identifier("customer.age > 1")
+        );
+        assert_eq!(Ok((".", "customer")), identifier("customer."));
+    }
+
+    #[test]
+    fn test_identifier_span() {
+        let source = "A && customer.is_vip";
+        let (_, identifier) = Identifier::parse(&source[5..]).unwrap();
+
+        assert_eq!("customer.is_vip", identifier.name);
+        assert_eq!(5..20, identifier.span.resolve(source));
+    }
+
+    /// A xorshift generator, so that the property test below is
+    /// reproducible and needs no extra dependencies.
+    struct Random(u64);
+
+    impl Random {
+        fn below(&mut self, bound: u64) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+
+            self.0 % bound
+        }
+
+        fn pick<'a>(&mut self, items: &[&'a str]) -> &'a str {
+            items[self.below(items.len() as u64) as usize]
+        }
+
+        /// Joins one to three operands with random operators.
+        fn chain(
+            &mut self,
+            operators: &[&str],
+            operand: impl Fn(&mut Self) -> String,
+        ) -> String {
+            let mut source = operand(self);
+
+            for _ in 0..self.below(3) {
+                source.push_str(self.pick(operators));
+                source.push_str(&operand(self));
+            }
+
+            source
+        }
+
+        fn arithmetic(&mut self, depth: u32) -> String {
+            let term = |random: &mut Self| {
+                let factor = |random: &mut Self| random.number(depth);
+
+                random.chain(&[" * ", "/", " × ", " ÷ "], factor)
+            };
+
+            self.chain(&[" + ", "-", " - "], term)
+        }
+
+        fn number(&mut self, depth: u32) -> String {
+            let choices = if depth == 0 { 3 } else { 6 };
+
+            match self.below(choices) {
+                0 => self.pick(&["D", "E", "F"]).into(),
+                1 => format!("{}", self.below(20) as i64 - 5),
+                2 => format!("{}.{}", self.below(10), self.below(100)),
+                3 => format!("({})", self.arithmetic(depth - 1)),
+                4 => format!(
+                    "if({}, {}, {})",
+                    self.boolean(depth - 1),
+                    self.arithmetic(depth - 1),
+                    self.arithmetic(depth - 1)
+                ),
+                _ => format!(
+                    "(let x = {} in x * {})",
+                    self.arithmetic(depth - 1),
+                    self.arithmetic(depth - 1)
+                ),
+            }
+        }
+
+        fn boolean(&mut self, depth: u32) -> String {
+            let term = |random: &mut Self| {
+                let factor = |random: &mut Self| random.condition(depth);
+                let operators =
+                    ["&&", " || ", " ^^ ", " and ", " or ", " xor ", "∧"];
+
+                random.chain(&operators, factor)
+            };
+            let implication =
+                |random: &mut Self| random.chain(&[" -> ", "->"], term);
+
+            self.chain(&[" <-> "], implication)
+        }
+
+        fn condition(&mut self, depth: u32) -> String {
+            let choices = if depth == 0 { 3 } else { 7 };
+
+            match self.below(choices) {
+                0 => self.pick(&["A", "B", "C"]).into(),
+                1 => format!("{}{}", self.pick(&["!", "not "]), "B"),
+                2 => self.pick(&["true", "false"]).into(),
+                3 => format!("({})", self.boolean(depth - 1)),
+                4 => format!("!({})", self.boolean(depth - 1)),
+                5 => format!(
+                    "{} {} {}",
+                    self.arithmetic(depth - 1),
+                    self.pick(&["==", "!=", "<", "<=", ">", ">=", "≤"]),
+                    self.arithmetic(depth - 1)
+                ),
+                _ => format!(
+                    "{} in [{}, {}]",
+                    self.arithmetic(depth - 1),
+                    self.arithmetic(depth - 1),
+                    self.number(0)
+                ),
+            }
+        }
+
+        fn context(&mut self) -> Context {
+            let mut context = Context::default();
+
+            for name in &["A", "B", "C"] {
+                let value = self.below(2) == 0;
+                context.variables.insert(name.to_string(), value.into());
+            }
+
+            for name in &["D", "E", "F"] {
+                let value = self.below(20) as i64 - 10;
+                context.variables.insert(name.to_string(), value.into());
+            }
+
+            context
+        }
+    }
+
+    /// Parses the whole source, printing it canonically.
+    fn canonical<T>(source: &str) -> (T, String)
+    where
+        T: Expression + fmt::Display,
+    {
+        let (rest, parsed) = T::parse(source).unwrap();
+        assert_eq!("", rest, "{} is not parsed completely", source);
+
+        let printed = parsed.to_string();
+
+        (parsed, printed)
+    }
+
+    /// Printing a parsed expression and parsing it back must yield an
+    /// equal tree, which evaluates to the same result and prints the same.
+    #[test]
+    fn test_canonical_round_trip() {
+        use crate::expression::arithmetic::Expression as Arithmetic;
+        use crate::expression::boolean::Expression as Boolean;
+
+        let mut random = Random(0x2545_f491_4f6c_dd1d);
+
+        for _ in 0..200 {
+            let context = random.context();
+
+            let source = random.arithmetic(2);
+            let (original, printed) = canonical::<Arithmetic>(&source);
+            let (parsed, reprinted) = canonical::<Arithmetic>(&printed);
+            // Values are compared printed, since NaN doesn't equal itself.
+            let expected: Result<Value> = original.reduce(&context);
+            let expected = expected.map(|value| value.to_string());
+            let actual: Result<Value> = parsed.reduce(&context);
+            let actual = actual.map(|value| value.to_string());
+
+            assert_eq!(original, parsed, "{}", source);
+            assert_eq!(printed, reprinted, "printed from {}", source);
+            assert_eq!(parsed, canonical::<Arithmetic>(&reprinted).0);
+            assert_eq!(expected.ok(), actual.ok(), "{} != {}", source, printed);
+
+            let source = random.boolean(2);
+            let (original, printed) = canonical::<Boolean>(&source);
+            let (parsed, reprinted) = canonical::<Boolean>(&printed);
+            let expected: Result<bool> = original.reduce(&context);
+            let actual: Result<bool> = parsed.reduce(&context);
+
+            assert_eq!(original, parsed, "{}", source);
+            assert_eq!(printed, reprinted, "printed from {}", source);
+            assert_eq!(parsed, canonical::<Boolean>(&reprinted).0);
+            assert_eq!(expected.ok(), actual.ok(), "{} != {}", source, printed);
+        }
+    }
+
+    #[test]
+    fn test_redundant_parentheses() {
+        use crate::expression::arithmetic::Expression as Arithmetic;
+        use crate::expression::boolean::Expression as Boolean;
+
+        let arithmetic = |source| canonical::<Arithmetic>(source).0;
+        let boolean = |source| canonical::<Boolean>(source).0;
+
+        assert_eq!(arithmetic("D * 2 + 1"), arithmetic("(D * 2) + 1"));
+        assert_eq!(arithmetic("D + E + F"), arithmetic("((D + E)) + F"));
+        assert_eq!(arithmetic("D * E"), arithmetic("(D) * (E)"));
+        assert_eq!(arithmetic("D"), arithmetic("((D))"));
+        assert_ne!(arithmetic("D + E + F"), arithmetic("D + (E + F)"));
+        assert_ne!(arithmetic("D * E + F"), arithmetic("D * (E + F)"));
+
+        assert_eq!(boolean("!A"), boolean("!(A)"));
+        assert_eq!(boolean("!!A"), boolean("!((!A))"));
+        assert_eq!(boolean("D > 1 && A"), boolean("(D > 1) && A"));
+        assert_eq!(boolean("A && B -> C"), boolean("((A && B)) -> C"));
+        assert_eq!(boolean("!(D > 1)"), boolean("!((D > 1))"));
+        assert_ne!(boolean("A -> B -> C"), boolean("A -> (B -> C)"));
+    }
+
+    #[test]
+    fn test_identifier_parsing_invalid() {
+        assert!(identifier("2024_rate").is_err());
+        assert!(identifier("True").is_err());
+        assert!(identifier(".age").is_err());
     }
 }
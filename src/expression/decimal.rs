@@ -0,0 +1,390 @@
+/// This module implements base-10 fixed-point numbers, so that monetary
+/// formulas don't drift in the last cent.
+use std::cmp::Ordering;
+use std::convert::TryFrom;
+use std::fmt;
+
+use anyhow::{Context as _, Result};
+use serde::{Deserialize, Serialize, Serializer};
+
+/// How results which don't fit into the scale are rounded.
+#[derive(Clone, Copy, Default, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Rounding {
+    /// Ties go to the even neighbour, also known as banker's rounding.
+    #[default]
+    HalfEven,
+    /// Ties go away from zero.
+    HalfUp,
+}
+
+/// How many fractional digits results are rounded to.
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub struct Precision {
+    pub digits: u32,
+    #[serde(default)]
+    pub rounding: Rounding,
+}
+
+/// Prints the precision as written in rules, such as `2, half_up`. The
+/// default rounding is left out.
+impl fmt::Display for Precision {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        match self.rounding {
+            Rounding::HalfEven => write!(formatter, "{}", self.digits),
+            Rounding::HalfUp => write!(formatter, "{}, half_up", self.digits),
+        }
+    }
+}
+
+/// A number with exactly `scale` fractional digits, stored as an integer
+/// amount of `10^-scale` units.
+#[derive(Clone, Copy, Debug)]
+pub struct Decimal {
+    units: i128,
+    scale: u32,
+    rounding: Rounding,
+}
+
+impl Decimal {
+    pub fn from_int(
+        value: i64,
+        scale: u32,
+        rounding: Rounding,
+    ) -> Result<Self> {
+        let units = rescale(value.into(), 0, scale, rounding)?;
+
+        Ok(Self {
+            units,
+            scale,
+            rounding,
+        })
+    }
+
+    /// Converts the float using its shortest representation, so that `0.1`
+    /// becomes exactly `0.1`.
+    pub fn from_f64(
+        value: f64,
+        scale: u32,
+        rounding: Rounding,
+    ) -> Result<Self> {
+        anyhow::ensure!(
+            value.is_finite(),
+            "Unable to represent {} as a decimal",
+            value
+        );
+
+        Self::parse(&value.to_string(), scale, rounding)
+    }
+
+    /// Parses a decimal literal, such as `-12.345` or `1.2e-3`, rounding it
+    /// to the scale.
+    pub fn parse(
+        literal: &str,
+        scale: u32,
+        rounding: Rounding,
+    ) -> Result<Self> {
+        let invalid = || format!("Invalid decimal {}", literal);
+
+        let (mantissa, exponent) = match literal.find(['e', 'E']) {
+            Some(index) => {
+                let exponent: i64 =
+                    literal[index + 1..].parse().ok().with_context(invalid)?;
+
+                (&literal[..index], exponent)
+            },
+            None => (literal, 0),
+        };
+        let (negative, digits) = match mantissa.strip_prefix('-') {
+            Some(digits) => (true, digits),
+            None => (false, mantissa.strip_prefix('+').unwrap_or(mantissa)),
+        };
+        let (whole, fraction) = match digits.find('.') {
+            Some(index) => (&digits[..index], &digits[index + 1..]),
+            None => (digits, ""),
+        };
+
+        let mut units: i128 = 0;
+        for digit in whole.chars().chain(fraction.chars()) {
+            let digit = digit.to_digit(10).with_context(invalid)?;
+
+            units = units
+                .checked_mul(10)
+                .and_then(|units| units.checked_add(digit.into()))
+                .with_context(invalid)?;
+        }
+
+        if negative {
+            units = -units;
+        }
+
+        // The digits are read as units of `10^-shift`, which a positive
+        // exponent turns into a negative shift.
+        let shift = fraction.len() as i64 - exponent;
+        let units = if shift >= 0 {
+            let shift = u32::try_from(shift).ok().with_context(invalid)?;
+
+            rescale(units, shift, scale, rounding)?
+        } else {
+            let shift = u32::try_from(-shift).ok().with_context(invalid)?;
+            let units = rescale(units, 0, shift, rounding)
+                .ok()
+                .with_context(invalid)?;
+
+            rescale(units, 0, scale, rounding)?
+        };
+
+        Ok(Self {
+            units,
+            scale,
+            rounding,
+        })
+    }
+
+    pub fn scale(self) -> u32 {
+        self.scale
+    }
+
+    pub fn rounding(self) -> Rounding {
+        self.rounding
+    }
+
+    /// Changes the scale, rounding the dropped digits using the given mode
+    /// rather than the decimal's own one.
+    pub fn round(self, scale: u32, rounding: Rounding) -> Result<Self> {
+        let units = rescale(self.units, self.scale, scale, rounding)?;
+
+        Ok(Self {
+            units,
+            scale,
+            rounding: self.rounding,
+        })
+    }
+
+    pub fn as_f64(self) -> f64 {
+        self.units as f64 / 10f64.powi(self.scale as _)
+    }
+
+    pub fn add(self, rhs: Self) -> Result<Self> {
+        let (lhs_units, rhs_units, scale) = self.align(rhs)?;
+        let units = lhs_units.checked_add(rhs_units);
+
+        self.with_units(units, scale)
+    }
+
+    pub fn subtract(self, rhs: Self) -> Result<Self> {
+        let (lhs_units, rhs_units, scale) = self.align(rhs)?;
+        let units = lhs_units.checked_sub(rhs_units);
+
+        self.with_units(units, scale)
+    }
+
+    pub fn multiply(self, rhs: Self) -> Result<Self> {
+        let scale = self.scale.max(rhs.scale);
+        let units = self.units.checked_mul(rhs.units).and_then(|units| {
+            rescale(units, self.scale + rhs.scale, scale, self.rounding).ok()
+        });
+
+        self.with_units(units, scale)
+    }
+
+    pub fn divide(self, rhs: Self) -> Result<Self> {
+        anyhow::ensure!(rhs.units != 0, "Division by zero");
+
+        // (a / 10^sa) / (b / 10^sb) * 10^s = a * 10^(s + sb - sa) / b
+        let scale = self.scale.max(rhs.scale);
+        let units =
+            rescale(self.units, self.scale, scale + rhs.scale, self.rounding)
+                .ok()
+                .map(|units| divide_rounded(units, rhs.units, self.rounding));
+
+        self.with_units(units, scale)
+    }
+
+    /// Brings both numbers to the same scale.
+    fn align(self, rhs: Self) -> Result<(i128, i128, u32)> {
+        let scale = self.scale.max(rhs.scale);
+        let lhs = rescale(self.units, self.scale, scale, self.rounding)?;
+        let rhs = rescale(rhs.units, rhs.scale, scale, self.rounding)?;
+
+        Ok((lhs, rhs, scale))
+    }
+
+    fn with_units(self, units: Option<i128>, scale: u32) -> Result<Self> {
+        let units = units.context("Decimal overflow")?;
+
+        Ok(Self {
+            units,
+            scale,
+            rounding: self.rounding,
+        })
+    }
+}
+
+/// Changes the number of fractional digits of `units`, rounding if some of
+/// them are dropped.
+fn rescale(
+    units: i128,
+    from: u32,
+    to: u32,
+    rounding: Rounding,
+) -> Result<i128> {
+    let overflow = || format!("Decimal scale {} is too large", to);
+
+    if to >= from {
+        10i128
+            .checked_pow(to - from)
+            .and_then(|factor| units.checked_mul(factor))
+            .with_context(overflow)
+    } else {
+        let factor = 10i128.checked_pow(from - to).with_context(overflow)?;
+
+        Ok(divide_rounded(units, factor, rounding))
+    }
+}
+
+fn divide_rounded(
+    numerator: i128,
+    denominator: i128,
+    rounding: Rounding,
+) -> i128 {
+    let quotient = numerator / denominator;
+    let remainder = (numerator % denominator).unsigned_abs();
+    let rest = denominator.unsigned_abs() - remainder;
+
+    let away_from_zero = match remainder.cmp(&rest) {
+        Ordering::Less => false,
+        Ordering::Greater => true,
+        Ordering::Equal => match rounding {
+            Rounding::HalfUp => true,
+            Rounding::HalfEven => quotient % 2 != 0,
+        },
+    };
+
+    if !away_from_zero || remainder == 0 {
+        quotient
+    } else if (numerator < 0) == (denominator < 0) {
+        quotient + 1
+    } else {
+        quotient - 1
+    }
+}
+
+impl PartialEq for Decimal {
+    fn eq(&self, other: &Self) -> bool {
+        self.partial_cmp(other) == Some(Ordering::Equal)
+    }
+}
+
+impl PartialOrd for Decimal {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        match self.align(*other) {
+            Ok((lhs, rhs, _)) => lhs.partial_cmp(&rhs),
+            Err(_) => self.as_f64().partial_cmp(&other.as_f64()),
+        }
+    }
+}
+
+impl fmt::Display for Decimal {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        let sign = if self.units < 0 { "-" } else { "" };
+        let digits = format!(
+            "{:0width$}",
+            self.units.unsigned_abs(),
+            width = self.scale as usize + 1
+        );
+        let (whole, fraction) =
+            digits.split_at(digits.len() - self.scale as usize);
+
+        if fraction.is_empty() {
+            write!(formatter, "{}{}", sign, whole)
+        } else {
+            write!(formatter, "{}{}.{}", sign, whole, fraction)
+        }
+    }
+}
+
+/// Decimals are serialized as strings, since JSON numbers are usually read
+/// as floats.
+impl Serialize for Decimal {
+    fn serialize<S: Serializer>(
+        &self,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decimal(literal: &str, rounding: Rounding) -> Decimal {
+        Decimal::parse(literal, 2, rounding).unwrap()
+    }
+
+    #[test]
+    fn test_parsing() {
+        let even = Rounding::HalfEven;
+
+        assert_eq!("12.35", decimal("12.345000001", even).to_string());
+        assert_eq!("12.34", decimal("12.345", even).to_string());
+        assert_eq!("12.35", decimal("12.345", Rounding::HalfUp).to_string());
+        assert_eq!("-0.05", decimal("-0.045", Rounding::HalfUp).to_string());
+        assert_eq!("7.00", decimal("7", even).to_string());
+        assert_eq!(
+            "0.10",
+            Decimal::from_f64(0.1, 2, even).unwrap().to_string()
+        );
+        assert!(Decimal::parse("1.2.3", 2, even).is_err());
+        assert_eq!("1200.00", decimal("1.2e3", even).to_string());
+        assert_eq!("0.12", decimal("+12.3E-2", even).to_string());
+        assert_eq!("0.00", decimal("-1e-3", even).to_string());
+        assert!(Decimal::parse("1e", 2, even).is_err());
+        assert_eq!(
+            "12.2",
+            decimal("12.25", even).round(1, even).unwrap().to_string()
+        );
+        assert_eq!(
+            "12.250",
+            decimal("12.25", even).round(3, even).unwrap().to_string()
+        );
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        let even = Rounding::HalfEven;
+        let price = decimal("19.99", even);
+        let count = Decimal::from_int(3, 2, even).unwrap();
+
+        assert_eq!("59.97", price.multiply(count).unwrap().to_string());
+        assert_eq!("6.66", price.divide(count).unwrap().to_string());
+        assert_eq!("22.99", price.add(count).unwrap().to_string());
+        assert_eq!("-16.99", count.subtract(price).unwrap().to_string());
+        assert_eq!(
+            "0.3",
+            Decimal::from_f64(0.1, 1, even)
+                .unwrap()
+                .add(Decimal::from_f64(0.2, 1, even).unwrap())
+                .unwrap()
+                .to_string()
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        let even = Rounding::HalfEven;
+        let zero = decimal("0", even);
+        let huge = Decimal::from_int(i64::MAX, 2, even).unwrap();
+
+        assert_eq!(
+            "Division by zero",
+            format!("{}", decimal("1", even).divide(zero).unwrap_err())
+        );
+        assert_eq!(
+            "Decimal overflow",
+            format!("{}", huge.multiply(huge).unwrap_err())
+        );
+        assert!(Decimal::from_f64(f64::NAN, 2, even).is_err());
+    }
+}
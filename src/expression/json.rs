@@ -0,0 +1,246 @@
+/// This module implements the JSON form of expressions, meant for tools
+/// which build rules rather than write them, such as visual editors.
+///
+/// The shape doesn't follow the parsed expressions: operations are binary
+/// nodes, which nest to the left the way chains of operators are applied,
+/// and parentheses are implied by the nesting.
+///
+/// ```json
+/// {"type": "binary", "operator": "&&",
+///  "lhs": {"type": "variable", "name": "A"},
+///  "rhs": {"type": "not", "operand": {"type": "variable", "name": "C"}}}
+/// ```
+use std::fmt;
+use std::str::FromStr;
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use serde_json::Value as Json;
+
+use super::common::{
+    identifier, Expression, ExpressionWithOperator, Identifier, Operator,
+};
+use super::number::Number;
+
+/// The version of the shape, which changes whenever the shape does.
+pub const VERSION: u32 = 1;
+
+/// A node of the JSON form, either of a condition or of a formula.
+#[derive(PartialEq, Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Node {
+    /// A number, a string or a boolean.
+    Literal {
+        value: Json,
+    },
+    Variable {
+        name: String,
+    },
+    Not {
+        operand: Box<Node>,
+    },
+    /// Any operation written between its operands, including comparisons,
+    /// spelled as in the text form, such as `&&`, `<=` or `*`.
+    Binary {
+        operator: String,
+        lhs: Box<Node>,
+        rhs: Box<Node>,
+    },
+    /// Set membership, such as `country in ["DE", "AT"]`.
+    In {
+        item: Box<Node>,
+        set: Vec<Node>,
+    },
+    If {
+        condition: Box<Node>,
+        then: Box<Node>,
+        otherwise: Box<Node>,
+    },
+    Let {
+        name: String,
+        value: Box<Node>,
+        body: Box<Node>,
+    },
+}
+
+/// Expressions which can be turned into nodes.
+pub trait ToNode {
+    fn to_node(&self) -> Node;
+}
+
+/// Expressions which can be built from nodes.
+pub trait FromNode: Sized {
+    fn from_node(node: &Node) -> Result<Self>;
+}
+
+impl Node {
+    pub fn binary(operator: impl fmt::Display, lhs: Node, rhs: Node) -> Self {
+        Self::Binary {
+            operator: operator.to_string(),
+            lhs: Box::new(lhs),
+            rhs: Box::new(rhs),
+        }
+    }
+
+    pub fn number(number: Number) -> Self {
+        Self::Literal {
+            value: serde_json::to_value(number).unwrap_or(Json::Null),
+        }
+    }
+
+    /// Whether the node is an operation, which parses as the operator.
+    pub fn is_operation<O: FromStr>(&self) -> bool {
+        match self {
+            Self::Binary { operator, .. } => operator.parse::<O>().is_ok(),
+            _ => false,
+        }
+    }
+
+    /// An error about the node found where something else was expected,
+    /// e.g. `Unexpected && in a formula`.
+    pub fn unexpected(&self, within: &str) -> anyhow::Error {
+        let found = match self {
+            Self::Literal { value } => value.to_string(),
+            Self::Variable { name } => name.clone(),
+            Self::Not { .. } => "!".into(),
+            Self::Binary { operator, .. } => operator.clone(),
+            Self::In { .. } => "in".into(),
+            Self::If { .. } => "if".into(),
+            Self::Let { .. } => "let".into(),
+        };
+
+        anyhow::anyhow!("Unexpected {} in {}", found, within)
+    }
+}
+
+/// Operations of a chain nest to the left, so that `D - E - F` becomes
+/// `(D - E) - F`.
+impl<T, O> ToNode for ExpressionWithOperator<T, O>
+where
+    T: Expression + ToNode,
+    O: Operator + fmt::Display,
+{
+    fn to_node(&self) -> Node {
+        self.tail
+            .iter()
+            .fold(self.head.to_node(), |lhs, (operator, item)| {
+                Node::binary(operator, lhs, item.to_node())
+            })
+    }
+}
+
+/// Left operands of the chain's operators extend the chain, whereas
+/// anything else is left to the operands, which parenthesize looser
+/// operations.
+impl<T, O> FromNode for ExpressionWithOperator<T, O>
+where
+    T: Expression + FromNode,
+    O: Operator,
+{
+    fn from_node(node: &Node) -> Result<Self> {
+        if let Node::Binary { operator, lhs, rhs } = node {
+            if let Ok(operator) = operator.parse::<O>() {
+                let mut chain = Self::from_node(lhs)?;
+                chain.tail.push((operator, T::from_node(rhs)?));
+
+                return Ok(chain);
+            }
+        }
+
+        T::from_node(node).map(Self::from)
+    }
+}
+
+impl FromNode for Number {
+    fn from_node(node: &Node) -> Result<Self> {
+        let value = match node {
+            Node::Literal {
+                value: Json::Number(value),
+            } => value,
+            _ => return Err(node.unexpected("a number")),
+        };
+
+        match (value.as_i64(), value.as_f64()) {
+            (Some(value), _) => Ok(Number::Int(value)),
+            (None, Some(value)) => Ok(Number::Float(value)),
+            (None, None) => Err(node.unexpected("a number")),
+        }
+    }
+}
+
+/// Checks the name is an identifier, such as `customer.age`, rather than
+/// a keyword or anything else.
+pub fn name(name: &str) -> Result<Identifier> {
+    match identifier(name) {
+        Ok(("", _)) => Ok(Identifier::from(name)),
+        _ => anyhow::bail!("Invalid name {}", name),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::expression::arithmetic::Expression as Arithmetic;
+    use crate::expression::boolean::Expression as Boolean;
+
+    fn round_trip<T>(source: &str) -> String
+    where
+        T: Expression + ToNode + FromNode + fmt::Display,
+    {
+        let node = T::parse(source).unwrap().1.to_node();
+        let json = serde_json::to_string(&node).unwrap();
+        let node: Node = serde_json::from_str(&json).unwrap();
+
+        T::from_node(&node).unwrap().to_string()
+    }
+
+    #[test]
+    fn test_node_shape() {
+        let node = Boolean::parse("A && !C").unwrap().1.to_node();
+        let expected = serde_json::json!({
+            "type": "binary",
+            "operator": "&&",
+            "lhs": {"type": "variable", "name": "A"},
+            "rhs": {
+                "type": "not",
+                "operand": {"type": "variable", "name": "C"},
+            },
+        });
+
+        assert_eq!(expected, serde_json::to_value(node).unwrap());
+    }
+
+    #[test]
+    fn test_round_trip() {
+        for &source in
+            &["D - (E - F) * 2.5", "if(A, D, 0) + (let x = E in x * x)"]
+        {
+            let expected = Arithmetic::parse(source).unwrap().1.to_string();
+
+            assert_eq!(expected, round_trip::<Arithmetic>(source));
+        }
+
+        for &source in &[
+            r#"(A || B) && !(D > 1) -> plan in ["pro", "team"]"#,
+            "A <-> (B <-> C) ^^ !(A && B)",
+        ] {
+            let expected = Boolean::parse(source).unwrap().1.to_string();
+
+            assert_eq!(expected, round_trip::<Boolean>(source));
+        }
+    }
+
+    #[test]
+    fn test_invalid_nodes() {
+        let node =
+            Node::binary("&&", Node::number(1.into()), Node::number(2.into()));
+        let error = Arithmetic::from_node(&node).unwrap_err();
+
+        assert_eq!("Unexpected && in a formula", error.to_string());
+
+        let node = Node::Variable { name: "in".into() };
+        let error = Boolean::from_node(&node).unwrap_err();
+
+        assert_eq!("Invalid name in", error.to_string());
+    }
+}
@@ -0,0 +1,337 @@
+/// This module defines numbers arithmetic expressions operate on. Integers
+/// are kept exact until they are mixed with floats.
+use std::cmp::Ordering;
+use std::fmt;
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use super::decimal::{Decimal, Precision, Rounding};
+
+#[derive(Clone, Copy, Debug, Serialize)]
+#[serde(untagged)]
+pub enum Number {
+    Int(i64),
+    Float(f64),
+    Decimal(Decimal),
+}
+
+/// How numbers are represented during the evaluation.
+#[derive(Clone, Copy, Default, PartialEq, Debug, Deserialize)]
+#[serde(tag = "mode", rename_all = "snake_case")]
+pub enum Arithmetic {
+    /// Integers are exact, the rest are IEEE 754 floats.
+    #[default]
+    Float,
+    /// All numbers are decimals with `scale` fractional digits.
+    Decimal { scale: u32, rounding: Rounding },
+}
+
+impl Arithmetic {
+    /// Converts constants and variables into the numbers of the mode.
+    pub fn convert(self, number: Number) -> Result<Number> {
+        match self {
+            Self::Float => Ok(number),
+            Self::Decimal { scale, rounding } => {
+                number.to_decimal(scale, rounding).map(Number::Decimal)
+            },
+        }
+    }
+}
+
+/// What to do when floats run into infinities. IEEE 754 is followed by
+/// default, by the library and the HTTP server alike.
+#[derive(Clone, Copy, Default, PartialEq, Debug, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FloatPolicy {
+    /// Follow IEEE 754, so that `-5.0 / 0` is negative infinity.
+    #[default]
+    Ieee,
+    /// Reject divisions by zero and results which are infinite or NaN.
+    NonFinite,
+    /// Reject divisions by zero only.
+    DivisionByZero,
+}
+
+impl FloatPolicy {
+    /// Checks the divisor before the division.
+    pub fn check_divisor(self, divisor: Number) -> Result<Number> {
+        if self != Self::Ieee && divisor == Number::Int(0) {
+            anyhow::bail!("Division by zero");
+        }
+
+        Ok(divisor)
+    }
+
+    /// Checks the result of an operation.
+    pub fn check(self, result: Number) -> Result<Number> {
+        match result {
+            Number::Float(value) if self == Self::NonFinite => {
+                anyhow::ensure!(value.is_finite(), "Result is not finite");
+
+                Ok(result)
+            },
+            _ => Ok(result),
+        }
+    }
+}
+
+impl Number {
+    pub fn as_f64(self) -> f64 {
+        match self {
+            Self::Int(value) => value as _,
+            Self::Float(value) => value,
+            Self::Decimal(value) => value.as_f64(),
+        }
+    }
+
+    pub fn to_decimal(self, scale: u32, rounding: Rounding) -> Result<Decimal> {
+        match self {
+            Self::Int(value) => Decimal::from_int(value, scale, rounding),
+            Self::Float(value) => Decimal::from_f64(value, scale, rounding),
+            Self::Decimal(value) => Ok(value),
+        }
+    }
+
+    /// Rounds the number to the given number of fractional digits. Floats
+    /// stay floats, the nearest ones to the rounded decimals.
+    pub fn round(self, precision: Precision) -> Result<Self> {
+        let Precision { digits, rounding } = precision;
+
+        match self {
+            Self::Int(_) => Ok(self),
+            // Such floats don't have any fractional digits to round.
+            Self::Float(value)
+                if value.is_nan() || value.abs() >= 2f64.powi(53) =>
+            {
+                Ok(self)
+            },
+            Self::Float(value) => Decimal::from_f64(value, digits, rounding)
+                .map(|rounded| Self::Float(rounded.as_f64())),
+            Self::Decimal(value) => {
+                value.round(digits, rounding).map(Self::Decimal)
+            },
+        }
+    }
+
+    pub fn checked_add(self, rhs: Self) -> Result<Self> {
+        self.apply(rhs, i64::checked_add, Decimal::add, |lhs, rhs| lhs + rhs)
+    }
+
+    pub fn checked_sub(self, rhs: Self) -> Result<Self> {
+        self.apply(rhs, i64::checked_sub, Decimal::subtract, |lhs, rhs| {
+            lhs - rhs
+        })
+    }
+
+    pub fn checked_mul(self, rhs: Self) -> Result<Self> {
+        self.apply(rhs, i64::checked_mul, Decimal::multiply, |lhs, rhs| {
+            lhs * rhs
+        })
+    }
+
+    /// Divides integers exactly whenever the divisor divides the dividend.
+    /// Otherwise, the quotient can't be an integer and becomes a float,
+    /// which is infinite for a zero divisor, see `FloatPolicy`.
+    pub fn checked_div(self, rhs: Self) -> Result<Self> {
+        match self.promote(rhs)? {
+            (Self::Decimal(lhs), Self::Decimal(rhs)) => {
+                lhs.divide(rhs).map(Self::Decimal)
+            },
+            (Self::Int(lhs), Self::Int(0)) => Ok(Self::Float(lhs as f64 / 0.0)),
+            (Self::Int(lhs), Self::Int(rhs)) => match lhs.checked_rem(rhs) {
+                Some(0) => Ok(Self::Int(lhs / rhs)),
+                Some(_) => Ok(Self::Float(lhs as f64 / rhs as f64)),
+                None => anyhow::bail!("Integer overflow"),
+            },
+            (lhs, rhs) => Ok(Self::Float(lhs.as_f64() / rhs.as_f64())),
+        }
+    }
+
+    /// A decimal turns the other operand into a decimal of the same scale.
+    fn promote(self, rhs: Self) -> Result<(Self, Self)> {
+        match (self, rhs) {
+            (Self::Decimal(lhs), rhs) => {
+                let rhs = rhs.to_decimal(lhs.scale(), lhs.rounding())?;
+
+                Ok((self, Self::Decimal(rhs)))
+            },
+            (lhs, Self::Decimal(decimal)) => {
+                let lhs =
+                    lhs.to_decimal(decimal.scale(), decimal.rounding())?;
+
+                Ok((Self::Decimal(lhs), rhs))
+            },
+            operands => Ok(operands),
+        }
+    }
+
+    fn apply(
+        self,
+        rhs: Self,
+        exact: fn(i64, i64) -> Option<i64>,
+        decimal: fn(Decimal, Decimal) -> Result<Decimal>,
+        inexact: fn(f64, f64) -> f64,
+    ) -> Result<Self> {
+        match self.promote(rhs)? {
+            (Self::Decimal(lhs), Self::Decimal(rhs)) => {
+                decimal(lhs, rhs).map(Self::Decimal)
+            },
+            (Self::Int(lhs), Self::Int(rhs)) => match exact(lhs, rhs) {
+                Some(result) => Ok(Self::Int(result)),
+                None => anyhow::bail!("Integer overflow"),
+            },
+            (lhs, rhs) => Ok(Self::Float(inexact(lhs.as_f64(), rhs.as_f64()))),
+        }
+    }
+}
+
+/// Numbers are compared by their values, so `2` equals `2.0`.
+impl PartialEq for Number {
+    fn eq(&self, other: &Self) -> bool {
+        self.partial_cmp(other) == Some(Ordering::Equal)
+    }
+}
+
+impl PartialOrd for Number {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        match self.promote(*other) {
+            Ok((Self::Int(lhs), Self::Int(rhs))) => lhs.partial_cmp(&rhs),
+            Ok((Self::Decimal(lhs), Self::Decimal(rhs))) => {
+                lhs.partial_cmp(&rhs)
+            },
+            _ => self.as_f64().partial_cmp(&other.as_f64()),
+        }
+    }
+}
+
+impl fmt::Display for Number {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Int(value) => write!(formatter, "{}", value),
+            // Unlike `Display`, `Debug` keeps the fractional part of whole
+            // floats, so that `2.0` isn't taken for an integer.
+            Self::Float(value) => write!(formatter, "{:?}", value),
+            Self::Decimal(value) => write!(formatter, "{}", value),
+        }
+    }
+}
+
+impl From<i64> for Number {
+    fn from(value: i64) -> Self {
+        Self::Int(value)
+    }
+}
+
+impl From<f64> for Number {
+    fn from(value: f64) -> Self {
+        Self::Float(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_exact_integers() {
+        let sum =
+            Number::Int(9_007_199_254_740_993).checked_add(Number::Int(2));
+        assert!(matches!(sum.unwrap(), Number::Int(9_007_199_254_740_995)));
+
+        let quotient = Number::Int(12).checked_div(Number::Int(4)).unwrap();
+        assert!(matches!(quotient, Number::Int(3)));
+
+        let quotient = Number::Int(5).checked_div(Number::Int(2)).unwrap();
+        assert!(matches!(quotient, Number::Float(value) if value == 2.5));
+
+        let product = Number::Int(3).checked_mul(Number::Float(0.5)).unwrap();
+        assert!(matches!(product, Number::Float(value) if value == 1.5));
+    }
+
+    #[test]
+    fn test_overflow() {
+        let error = Number::Int(i64::MAX)
+            .checked_add(Number::Int(1))
+            .unwrap_err();
+        assert_eq!("Integer overflow", format!("{}", error));
+
+        let error = Number::Int(i64::MIN)
+            .checked_div(Number::Int(-1))
+            .unwrap_err();
+        assert_eq!("Integer overflow", format!("{}", error));
+
+        let quotient = Number::Int(-1).checked_div(Number::Int(0)).unwrap();
+        assert!(
+            matches!(quotient, Number::Float(value) if value == f64::NEG_INFINITY)
+        );
+    }
+
+    #[test]
+    fn test_decimals() {
+        let arithmetic = Arithmetic::Decimal {
+            scale: 2,
+            rounding: Rounding::HalfEven,
+        };
+        let price = arithmetic.convert(Number::Float(0.1)).unwrap();
+        let sum = price
+            .checked_add(Number::Float(0.2))
+            .unwrap()
+            .checked_mul(3.into());
+
+        assert_eq!("0.90", sum.unwrap().to_string());
+        assert_eq!(
+            "0.03",
+            price.checked_div(Number::Int(3)).unwrap().to_string()
+        );
+        assert_eq!(Number::Int(3), arithmetic.convert(3.into()).unwrap());
+    }
+
+    #[test]
+    fn test_float_policies() {
+        let infinity = Number::Float(f64::INFINITY);
+        let zero = Number::Float(0.0);
+
+        assert!(FloatPolicy::Ieee.check_divisor(zero).is_ok());
+        assert!(FloatPolicy::Ieee.check(infinity).is_ok());
+        assert!(FloatPolicy::DivisionByZero.check_divisor(zero).is_err());
+        assert!(FloatPolicy::DivisionByZero.check(infinity).is_ok());
+        assert!(FloatPolicy::NonFinite.check_divisor(zero).is_err());
+        assert_eq!(
+            "Result is not finite",
+            format!("{}", FloatPolicy::NonFinite.check(infinity).unwrap_err())
+        );
+    }
+
+    #[test]
+    fn test_rounding() {
+        let precision = Precision {
+            digits: 2,
+            rounding: Rounding::HalfUp,
+        };
+
+        assert_eq!(
+            "1.05",
+            Number::Float(1.0500000000000003)
+                .round(precision)
+                .unwrap()
+                .to_string()
+        );
+        // The shortest representation is rounded, not the binary one.
+        assert_eq!(
+            "2.68",
+            Number::Float(2.675).round(precision).unwrap().to_string()
+        );
+        assert_eq!("42", Number::Int(42).round(precision).unwrap().to_string());
+        assert!(matches!(
+            Number::Float(f64::INFINITY).round(precision),
+            Ok(Number::Float(value)) if value.is_infinite()
+        ));
+    }
+
+    #[test]
+    fn test_comparison() {
+        assert_eq!(Number::Int(2), Number::Float(2.0));
+        assert!(Number::Int(i64::MAX - 1) < Number::Int(i64::MAX));
+    }
+}
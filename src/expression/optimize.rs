@@ -0,0 +1,156 @@
+/// This module implements an optional optimization pass, which folds
+/// constants and drops operations which don't change anything, such as
+/// `D * 1` or `A && true`.
+///
+/// Optimized expressions evaluate to the same results, although errors of
+/// operations which no longer take place, such as a missing variable in
+/// `A && false`, are not reported anymore.
+use super::common::{Context, Expression, ExpressionWithOperator, Operator};
+use super::number::Number;
+use super::value::Value;
+
+pub trait Optimize: Sized {
+    fn optimize(self, context: &Context) -> Self;
+}
+
+/// Operands of the operators, which may turn out to be constants.
+pub trait Operand: Sized {
+    fn constant(&self) -> Option<Value>;
+
+    fn from_constant(value: Value) -> Option<Self>;
+
+    /// Whether the operand is the negation of the other one, as `!A` is
+    /// of `A`.
+    fn is_negation_of(&self, _other: &Self) -> bool {
+        false
+    }
+}
+
+/// Algebraic properties of the operators.
+pub trait Identities {
+    /// Applies the operator to constants, unless that fails.
+    fn fold(&self, lhs: Value, rhs: Value, context: &Context) -> Option<Value>;
+
+    /// Whether `x op constant` is `x`.
+    fn is_right_identity(&self, constant: &Value) -> bool;
+
+    /// Whether `constant op x` is `x`.
+    fn is_left_identity(&self, constant: &Value) -> bool;
+
+    /// Whether `x op constant` is `constant`, whatever `x` is.
+    fn absorbs(&self, _constant: &Value) -> bool {
+        false
+    }
+
+    /// The result of `x op !x`, if it is a constant.
+    fn complement(&self) -> Option<Value> {
+        None
+    }
+}
+
+impl<T, O> Operand for ExpressionWithOperator<T, O>
+where
+    T: Expression + Operand,
+    O: Operator,
+{
+    fn constant(&self) -> Option<Value> {
+        if self.tail.is_empty() {
+            self.head.constant()
+        } else {
+            None
+        }
+    }
+
+    fn from_constant(value: Value) -> Option<Self> {
+        T::from_constant(value).map(Self::from)
+    }
+
+    fn is_negation_of(&self, other: &Self) -> bool {
+        self.tail.is_empty()
+            && other.tail.is_empty()
+            && self.head.is_negation_of(&other.head)
+    }
+}
+
+impl<T, O> From<T> for ExpressionWithOperator<T, O>
+where
+    T: Expression,
+    O: Operator,
+{
+    fn from(head: T) -> Self {
+        Self { head, tail: vec![] }
+    }
+}
+
+/// Operands are optimized first, and then the operations are applied left
+/// to right, as long as the outcome is known upfront.
+impl<T, O> Optimize for ExpressionWithOperator<T, O>
+where
+    T: Expression + Operand + Optimize,
+    O: Operator + Identities,
+{
+    fn optimize(self, context: &Context) -> Self {
+        let mut result = Self::from(self.head.optimize(context));
+
+        for (operator, item) in self.tail {
+            let item = item.optimize(context);
+            let folded = match (result.constant(), item.constant()) {
+                (Some(lhs), Some(rhs)) => operator
+                    .fold(lhs, rhs, context)
+                    .and_then(Self::from_constant),
+                _ => None,
+            };
+
+            if let Some(folded) = folded {
+                result = folded;
+                continue;
+            }
+
+            match (result.constant(), item.constant()) {
+                (_, Some(rhs)) if operator.is_right_identity(&rhs) => {},
+                (_, Some(rhs)) if operator.absorbs(&rhs) => {
+                    result = Self::from(item);
+                },
+                (Some(lhs), _) if operator.is_left_identity(&lhs) => {
+                    result = Self::from(item);
+                },
+                _ => {
+                    let complement = operator.complement().filter(|_| {
+                        result.tail.is_empty()
+                            && result.head.is_negation_of(&item)
+                    });
+
+                    match complement.and_then(Self::from_constant) {
+                        Some(constant) => result = constant,
+                        None => result.tail.push((operator, item)),
+                    }
+                },
+            }
+        }
+
+        result
+    }
+}
+
+/// Whether the value is the integer, rather than a float equal to it, so
+/// that dropping `* 1` doesn't turn a float into an integer.
+pub fn is_integer(value: &Value, integer: i64) -> bool {
+    matches!(value, Value::Number(Number::Int(value)) if *value == integer)
+}
+
+/// Applies an arithmetic operation to constants, as evaluation would,
+/// unless the result is an error or not finite.
+pub fn fold_numbers(
+    lhs: Value,
+    rhs: Value,
+    context: &Context,
+    apply: fn(Number, Number) -> anyhow::Result<Number>,
+) -> Option<Value> {
+    let lhs = context.arithmetic.convert(lhs.as_number().ok()?).ok()?;
+    let rhs = context.arithmetic.convert(rhs.as_number().ok()?).ok()?;
+
+    match apply(lhs, rhs).ok()? {
+        Number::Float(value) if !value.is_finite() => None,
+        result => Some(Value::Number(result)),
+    }
+}
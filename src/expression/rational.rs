@@ -0,0 +1,214 @@
+/// This module implements exact fractions, used to evaluate formulas
+/// without any rounding at all.
+use std::cmp::Ordering;
+use std::fmt;
+
+use anyhow::{Context as _, Result};
+
+use super::bigint::BigInt;
+use super::number::Number;
+
+/// A fraction in lowest terms, with a positive denominator.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Rational {
+    numerator: BigInt,
+    denominator: BigInt,
+}
+
+impl Rational {
+    pub fn new(numerator: BigInt, denominator: BigInt) -> Result<Self> {
+        anyhow::ensure!(!denominator.is_zero(), "Division by zero");
+
+        let divisor = numerator.gcd(&denominator);
+        let divisor = if denominator.is_negative() {
+            divisor.negate()
+        } else {
+            divisor
+        };
+
+        Ok(Self {
+            numerator: numerator.div_rem(&divisor).0,
+            denominator: denominator.div_rem(&divisor).0,
+        })
+    }
+
+    /// Converts the number exactly. Floats are taken by their shortest
+    /// representation, so that `25.5` is `51/2` and `0.1` is `1/10`.
+    pub fn from_number(number: Number) -> Result<Self> {
+        match number {
+            Number::Int(value) => Ok(value.into()),
+            Number::Float(value) => {
+                anyhow::ensure!(
+                    value.is_finite(),
+                    "Unable to represent {} as a rational",
+                    value
+                );
+
+                Self::parse(&value.to_string())
+            },
+            Number::Decimal(value) => Self::parse(&value.to_string()),
+        }
+    }
+
+    /// Parses a plain decimal literal, such as `-12.345`.
+    pub fn parse(literal: &str) -> Result<Self> {
+        let invalid = || format!("Invalid number {}", literal);
+
+        let (negative, digits) = match literal.strip_prefix('-') {
+            Some(digits) => (true, digits),
+            None => (false, literal),
+        };
+        let (whole, fraction) = match digits.find('.') {
+            Some(index) => (&digits[..index], &digits[index + 1..]),
+            None => (digits, ""),
+        };
+
+        let ten = BigInt::from(10);
+        let mut numerator = BigInt::zero();
+        for digit in whole.chars().chain(fraction.chars()) {
+            let digit = digit.to_digit(10).with_context(invalid)?;
+
+            numerator = numerator.mul(&ten).add(&BigInt::from(digit as i64));
+        }
+
+        if negative {
+            numerator = numerator.negate();
+        }
+
+        Self::new(numerator, BigInt::pow10(fraction.len()))
+    }
+
+    pub fn add(&self, rhs: &Self) -> Result<Self> {
+        Self::new(
+            self.numerator
+                .mul(&rhs.denominator)
+                .add(&rhs.numerator.mul(&self.denominator)),
+            self.denominator.mul(&rhs.denominator),
+        )
+    }
+
+    pub fn subtract(&self, rhs: &Self) -> Result<Self> {
+        self.add(&Self {
+            numerator: rhs.numerator.negate(),
+            denominator: rhs.denominator.clone(),
+        })
+    }
+
+    pub fn multiply(&self, rhs: &Self) -> Result<Self> {
+        Self::new(
+            self.numerator.mul(&rhs.numerator),
+            self.denominator.mul(&rhs.denominator),
+        )
+    }
+
+    pub fn divide(&self, rhs: &Self) -> Result<Self> {
+        anyhow::ensure!(!rhs.numerator.is_zero(), "Division by zero");
+
+        Self::new(
+            self.numerator.mul(&rhs.denominator),
+            self.denominator.mul(&rhs.numerator),
+        )
+    }
+
+    pub fn abs(&self) -> Self {
+        Self {
+            numerator: self.numerator.abs(),
+            denominator: self.denominator.clone(),
+        }
+    }
+
+    /// The nearest float, give or take a rounding of the lowest bits.
+    pub fn to_f64(&self) -> f64 {
+        // Scale the fraction so that the quotient keeps 64 significant bits.
+        let shift = 64 + self.denominator.bit_length() as i32
+            - self.numerator.bit_length() as i32;
+        let quotient = if shift >= 0 {
+            self.numerator.shl(shift as _).div_rem(&self.denominator).0
+        } else {
+            let denominator = self.denominator.shl(-shift as _);
+
+            self.numerator.div_rem(&denominator).0
+        };
+
+        quotient.to_f64() * 2f64.powi(-shift)
+    }
+}
+
+impl From<i64> for Rational {
+    fn from(value: i64) -> Self {
+        Self {
+            numerator: value.into(),
+            denominator: 1.into(),
+        }
+    }
+}
+
+impl Ord for Rational {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // Denominators are positive, so cross-multiplying keeps the order.
+        self.numerator
+            .mul(&other.denominator)
+            .cmp(&other.numerator.mul(&self.denominator))
+    }
+}
+
+impl PartialOrd for Rational {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl fmt::Display for Rational {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        if self.denominator == 1.into() {
+            write!(formatter, "{}", self.numerator)
+        } else {
+            write!(formatter, "{}/{}", self.numerator, self.denominator)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rational(literal: &str) -> Rational {
+        Rational::parse(literal).unwrap()
+    }
+
+    #[test]
+    fn test_conversions() {
+        assert_eq!("51/2", rational("25.5").to_string());
+        assert_eq!("-1/8", rational("-0.125").to_string());
+        assert_eq!(
+            "1/10",
+            Rational::from_number(Number::Float(0.1))
+                .unwrap()
+                .to_string()
+        );
+        assert_eq!(0.1, rational("0.1").to_f64());
+        assert_eq!(-2.5, rational("-2.5").to_f64());
+        assert!(Rational::from_number(Number::Float(f64::NAN)).is_err());
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        let third = Rational::from(1).divide(&3.into()).unwrap();
+        let sum = third.add(&third).unwrap().add(&third).unwrap();
+
+        assert_eq!(Rational::from(1), sum);
+        assert_eq!(
+            "3/10",
+            rational("0.1").add(&rational("0.2")).unwrap().to_string()
+        );
+        assert_eq!(
+            "-1/6",
+            third.subtract(&rational("0.5")).unwrap().to_string()
+        );
+        assert!(third < rational("0.34"));
+        assert_eq!(
+            "Division by zero",
+            format!("{}", third.divide(&0.into()).unwrap_err())
+        );
+    }
+}
@@ -0,0 +1,388 @@
+/// This module implements whole rules, as written in rule files or sent
+/// in requests, either in the text form or as a versioned JSON AST:
+///
+/// ```json
+/// {"version": 1, "rule": "arithmetic", "labels": ["M"],
+///  "outputs": [{"name": "K", "value": {"type": "variable", "name": "D"}}]}
+/// ```
+///
+/// See `json::Node` for the shape of the expressions.
+use std::fmt;
+
+use anyhow::Result;
+use serde::de::Error as _;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::Value as Json;
+
+use super::arithmetic::malformed_number;
+use super::arithmetic::parse as parse_arithmetic_expression;
+use super::arithmetic::RESERVED_OUTPUTS;
+use super::arithmetic::{parse_constant, parse_precision};
+use super::arithmetic::{Expression, Formula, Matcher, Output};
+use super::boolean::parse as parse_boolean_expression;
+use super::boolean::parse_default;
+use super::boolean::Expression as BoolExpression;
+use super::common::Span;
+use super::decimal::Precision;
+use super::json::{self, FromNode, Node, ToNode, VERSION};
+
+/// A single rule, see `Solver::add` for the kinds of rules.
+#[derive(PartialEq, Debug)]
+pub enum Rule {
+    /// `else => H = NONE`.
+    Default(String),
+    /// `const RATE_P = 25.5`.
+    Constant(String, Expression),
+    /// `precision 2, half_up`.
+    Precision(Precision),
+    /// `A && B && !C => H = M`.
+    Boolean(BoolExpression, String),
+    /// `H = M => K = D * 2`.
+    Arithmetic(Formula),
+}
+
+/// A rule as given in requests, either in the text form or as a JSON AST.
+#[derive(PartialEq, Debug, Deserialize)]
+#[serde(untagged)]
+pub enum Source {
+    Text(String),
+    Ast(Json),
+}
+
+impl Rule {
+    /// Parses a rule written down, trying the kinds of rules in turn. The
+    /// leftovers of a kind, such as `% 2` of `D % 2`, mean the rule is of
+    /// another kind, or else malformed.
+    pub fn parse(input: &str) -> Result<Self> {
+        if let Ok(("", label)) = parse_default(input) {
+            return Ok(Self::Default(label));
+        }
+
+        if let Ok(("", (name, expression))) = parse_constant(input) {
+            return Ok(Self::Constant(name, expression));
+        }
+
+        if let Ok(("", precision)) = parse_precision(input) {
+            return Ok(Self::Precision(precision));
+        }
+
+        if let Ok(("", (expression, label))) = parse_boolean_expression(input) {
+            return Ok(Self::Boolean(expression, label));
+        }
+
+        if let Ok(("", formula)) = parse_arithmetic_expression(input) {
+            if let Some((_, output)) = formula.duplicate_output() {
+                let range = output.span.resolve(input);
+
+                anyhow::bail!(
+                    "Duplicate output at {}..{} ({}): {} is already assigned",
+                    range.start,
+                    range.end,
+                    &input[range.clone()],
+                    output.name
+                );
+            }
+
+            return Ok(Self::Arithmetic(formula));
+        }
+
+        if let Some(error) = malformed_number(input) {
+            return Err(error);
+        }
+
+        anyhow::bail!("Unable to parse the expression {}", input)
+    }
+}
+
+impl Source {
+    /// Parses the text, or reads the JSON AST, of the rule.
+    pub fn rule(&self) -> Result<Rule> {
+        match self {
+            Self::Text(text) => Rule::parse(text),
+            Self::Ast(ast) => Rule::deserialize(ast)
+                .map_err(|error| anyhow::anyhow!("Malformed rule: {}", error)),
+        }
+    }
+}
+
+impl fmt::Display for Rule {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Default(label) => write!(formatter, "else => H = {}", label),
+            Self::Constant(name, expression) => {
+                write!(formatter, "const {} = {}", name, expression)
+            },
+            Self::Precision(precision) => {
+                write!(formatter, "precision {}", precision)
+            },
+            Self::Boolean(expression, label) => {
+                write!(formatter, "{} => H = {}", expression, label)
+            },
+            Self::Arithmetic(formula) => write!(formatter, "{}", formula),
+        }
+    }
+}
+
+/// The JSON form of a rule, tagged with the version of the shape.
+#[derive(Serialize, Deserialize)]
+struct Document {
+    version: u32,
+    #[serde(flatten)]
+    rule: RuleNode,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "rule", rename_all = "snake_case")]
+enum RuleNode {
+    Default {
+        label: String,
+    },
+    Constant {
+        name: String,
+        value: Node,
+    },
+    Precision(Precision),
+    Boolean {
+        condition: Node,
+        label: String,
+    },
+    /// The labels are `["*"]` for a wildcard rule.
+    Arithmetic {
+        labels: Vec<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        guard: Option<Node>,
+        outputs: Vec<OutputNode>,
+    },
+}
+
+#[derive(Serialize, Deserialize)]
+struct OutputNode {
+    name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    precision: Option<Precision>,
+    value: Node,
+}
+
+impl From<&Rule> for Document {
+    fn from(rule: &Rule) -> Self {
+        let rule = match rule {
+            Rule::Default(label) => RuleNode::Default {
+                label: label.clone(),
+            },
+            Rule::Constant(name, expression) => RuleNode::Constant {
+                name: name.clone(),
+                value: expression.to_node(),
+            },
+            Rule::Precision(precision) => RuleNode::Precision(*precision),
+            Rule::Boolean(expression, label) => RuleNode::Boolean {
+                condition: expression.to_node(),
+                label: label.clone(),
+            },
+            Rule::Arithmetic(formula) => RuleNode::Arithmetic {
+                labels: match &formula.matcher {
+                    Matcher::Labels(labels) => labels.clone(),
+                    Matcher::Wildcard => vec!["*".into()],
+                },
+                guard: formula.guard.as_ref().map(ToNode::to_node),
+                outputs: formula
+                    .outputs
+                    .iter()
+                    .map(|output| OutputNode {
+                        name: output.name.clone(),
+                        precision: output.precision,
+                        value: output.expression.to_node(),
+                    })
+                    .collect(),
+            },
+        };
+
+        Self {
+            version: VERSION,
+            rule,
+        }
+    }
+}
+
+impl Document {
+    fn into_rule(self) -> Result<Rule> {
+        if self.version != VERSION {
+            anyhow::bail!("Unsupported version {}", self.version);
+        }
+
+        let rule = match self.rule {
+            RuleNode::Default { label } => Rule::Default(label_name(label)?),
+            RuleNode::Constant { name, value } => Rule::Constant(
+                json::name(&name)?.name,
+                Expression::from_node(&value)?,
+            ),
+            RuleNode::Precision(precision) => Rule::Precision(precision),
+            RuleNode::Boolean { condition, label } => Rule::Boolean(
+                BoolExpression::from_node(&condition)?,
+                label_name(label)?,
+            ),
+            RuleNode::Arithmetic { outputs, .. } if outputs.is_empty() => {
+                anyhow::bail!("A rule must assign some outputs")
+            },
+            RuleNode::Arithmetic {
+                labels,
+                guard,
+                outputs,
+            } => {
+                let formula = Formula {
+                    matcher: matcher(labels)?,
+                    guard: guard
+                        .as_ref()
+                        .map(FromNode::from_node)
+                        .transpose()?,
+                    outputs: outputs
+                        .into_iter()
+                        .map(OutputNode::into_output)
+                        .collect::<Result<_>>()?,
+                };
+
+                if let Some((index, output)) = formula.duplicate_output() {
+                    anyhow::bail!(
+                        "Duplicate output at outputs[{}]: {} is already \
+                         assigned",
+                        index,
+                        output.name
+                    );
+                }
+
+                Rule::Arithmetic(formula)
+            },
+        };
+
+        Ok(rule)
+    }
+}
+
+impl OutputNode {
+    fn into_output(self) -> Result<Output> {
+        if RESERVED_OUTPUTS.contains(&self.name.as_str()) {
+            anyhow::bail!("An output can't be named {}", self.name);
+        }
+
+        Ok(Output {
+            name: json::name(&self.name)?.name,
+            span: Span::default(),
+            precision: self.precision,
+            expression: Expression::from_node(&self.value)?,
+        })
+    }
+}
+
+fn label_name(label: String) -> Result<String> {
+    json::name(&label).map(|_| label)
+}
+
+fn matcher(labels: Vec<String>) -> Result<Matcher> {
+    match labels.as_slice() {
+        [] => anyhow::bail!("A rule must match some labels"),
+        [wildcard] if wildcard == "*" => Ok(Matcher::Wildcard),
+        _ => labels
+            .into_iter()
+            .map(label_name)
+            .collect::<Result<_>>()
+            .map(Matcher::Labels),
+    }
+}
+
+impl Serialize for Rule {
+    fn serialize<S: Serializer>(
+        &self,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        Document::from(self).serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Rule {
+    fn deserialize<D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Self, D::Error> {
+        Document::deserialize(deserializer)?
+            .into_rule()
+            .map_err(D::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_json_round_trip() {
+        for &source in &[
+            "else => H = NONE",
+            "const RATE_P = 25.5",
+            "precision 2, half_up",
+            "A && (B || !C) => H = M",
+            "H = M | P && D > 100 => K[2, half_up] = D * 0.9, tax = K / 10",
+            "H = * => K = D",
+        ] {
+            let rule = Rule::parse(source).unwrap();
+            let json = serde_json::to_value(&rule).unwrap();
+
+            assert_eq!(VERSION, json["version"]);
+            assert_eq!(rule, serde_json::from_value(json).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_sources() {
+        let ast = serde_json::json!({
+            "version": 1,
+            "rule": "arithmetic",
+            "labels": ["M"],
+            "outputs": [{
+                "name": "K",
+                "value": {
+                    "type": "binary",
+                    "operator": "+",
+                    "lhs": {"type": "variable", "name": "D"},
+                    "rhs": {"type": "literal", "value": 2},
+                },
+            }],
+        });
+        let source: Source = serde_json::from_value(ast.clone()).unwrap();
+
+        let expected = Rule::parse("H = M => K = D + 2").unwrap();
+
+        assert_eq!(expected, source.rule().unwrap());
+
+        let mut future = ast;
+        future["version"] = 2.into();
+        let error = Source::Ast(future).rule().unwrap_err();
+
+        assert_eq!("Malformed rule: Unsupported version 2", error.to_string());
+    }
+
+    #[test]
+    fn test_duplicate_outputs() {
+        let error = Rule::parse("H = M => K = D, tax = K, K = 2").unwrap_err();
+
+        assert_eq!(
+            "Duplicate output at 25..26 (K): K is already assigned",
+            error.to_string()
+        );
+
+        let literal = serde_json::json!({"type": "literal", "value": 2});
+        let ast = serde_json::json!({
+            "version": 1,
+            "rule": "arithmetic",
+            "labels": ["M"],
+            "outputs": [
+                {"name": "K", "value": literal},
+                {"name": "K", "value": literal},
+            ],
+        });
+        let error = Source::Ast(ast).rule().unwrap_err();
+
+        assert_eq!(
+            "Malformed rule: Duplicate output at outputs[1]: K is already \
+             assigned",
+            error.to_string()
+        );
+    }
+}
@@ -0,0 +1,116 @@
+/// This module implements Kleene's three-valued logic, used to evaluate
+/// conditions when only some of the variables are known.
+use std::fmt;
+use std::ops::Not;
+
+use anyhow::Result;
+
+use super::common::Context;
+
+/// Expressions which can be reduced when only some of the variables are
+/// known.
+pub trait PartiallyReducible {
+    fn reduce_partially(&self, context: &Context) -> Result<Truth>;
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Truth {
+    True,
+    False,
+    /// Either true or false, depending on the missing variables.
+    Unknown,
+}
+
+impl Truth {
+    pub fn and(self, other: Self) -> Self {
+        match (self, other) {
+            (Self::False, _) | (_, Self::False) => Self::False,
+            (Self::True, Self::True) => Self::True,
+            _ => Self::Unknown,
+        }
+    }
+
+    pub fn or(self, other: Self) -> Self {
+        !(!self).and(!other)
+    }
+
+    pub fn xor(self, other: Self) -> Self {
+        match (self.known(), other.known()) {
+            (Some(lhs), Some(rhs)) => (lhs ^ rhs).into(),
+            _ => Self::Unknown,
+        }
+    }
+
+    pub fn implies(self, other: Self) -> Self {
+        (!self).or(other)
+    }
+
+    pub fn iff(self, other: Self) -> Self {
+        !self.xor(other)
+    }
+
+    /// The boolean value, unless it is unknown.
+    pub fn known(self) -> Option<bool> {
+        match self {
+            Self::True => Some(true),
+            Self::False => Some(false),
+            Self::Unknown => None,
+        }
+    }
+}
+
+impl Not for Truth {
+    type Output = Self;
+
+    fn not(self) -> Self {
+        match self {
+            Self::True => Self::False,
+            Self::False => Self::True,
+            Self::Unknown => Self::Unknown,
+        }
+    }
+}
+
+impl From<bool> for Truth {
+    fn from(value: bool) -> Self {
+        if value {
+            Self::True
+        } else {
+            Self::False
+        }
+    }
+}
+
+impl fmt::Display for Truth {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::True => formatter.write_str("true"),
+            Self::False => formatter.write_str("false"),
+            Self::Unknown => formatter.write_str("unknown"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Truth::*;
+
+    #[test]
+    fn test_kleene_conjunction() {
+        assert_eq!(False, Unknown.and(False));
+        assert_eq!(Unknown, Unknown.and(True));
+        assert_eq!(True, True.and(True));
+        assert_eq!(True, Unknown.or(True));
+        assert_eq!(Unknown, Unknown.or(False));
+        assert_eq!(Unknown, !Unknown);
+    }
+
+    #[test]
+    fn test_kleene_derived_operators() {
+        assert_eq!(Unknown, Unknown.xor(True));
+        assert_eq!(True, False.implies(Unknown));
+        assert_eq!(Unknown, True.implies(Unknown));
+        assert_eq!(False, True.iff(False));
+        assert_eq!(Unknown, Unknown.iff(Unknown));
+    }
+}
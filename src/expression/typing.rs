@@ -0,0 +1,106 @@
+/// This module is used to check types of expressions before they are
+/// evaluated, so that rules like `A + 1` are rejected upfront.
+use std::collections::HashMap;
+use std::fmt;
+
+use super::common::Span;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Type {
+    Bool,
+    Number,
+    String,
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Self::Bool => "bool",
+            Self::Number => "number",
+            Self::String => "string",
+        };
+
+        formatter.write_str(name)
+    }
+}
+
+/// Declared types of variables. Variables which are not declared are only
+/// checked during the evaluation.
+pub type Types = HashMap<String, Type>;
+
+#[derive(PartialEq, Debug)]
+pub struct TypeError {
+    pub message: String,
+    pub span: Span,
+}
+
+impl TypeError {
+    pub fn new(message: String, span: Option<Span>) -> Self {
+        // Only the operands of number-typed operators lack spans, and those
+        // never cause errors.
+        let span = span.unwrap_or_default();
+
+        Self { message, span }
+    }
+
+    /// Turns the error into a human-readable one, pointing at the part of
+    /// the source the error was caused by.
+    pub fn locate(self, source: &str) -> anyhow::Error {
+        let range = self.span.resolve(source);
+
+        anyhow::anyhow!(
+            "Type error at {}..{} ({}): {}",
+            range.start,
+            range.end,
+            &source[range.clone()],
+            self.message
+        )
+    }
+
+    /// Turns the error into a human-readable one, for rules which were not
+    /// written down, so there is nothing to point at.
+    pub fn explain(self) -> anyhow::Error {
+        anyhow::anyhow!("Type error: {}", self.message)
+    }
+}
+
+pub trait TypeCheck {
+    /// Infers the type of the expression, checking its parts on the way.
+    /// `None` means that the type depends on undeclared variables.
+    fn type_check(&self, types: &Types) -> Result<Option<Type>, TypeError>;
+
+    /// The location used to report errors about the expression.
+    fn span(&self) -> Option<Span>;
+}
+
+/// Checks that the expression has the expected type.
+pub fn expect<T: TypeCheck>(
+    expression: &T,
+    expected: Type,
+    types: &Types,
+) -> Result<(), TypeError> {
+    match expression.type_check(types)? {
+        Some(found) if found != expected => Err(TypeError::new(
+            format!("expected {}, found {}", expected, found),
+            expression.span(),
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Checks that both expressions have the same type, whatever it is.
+pub fn unify<L: TypeCheck, R: TypeCheck>(
+    lhs: &L,
+    rhs: &R,
+    types: &Types,
+) -> Result<Option<Type>, TypeError> {
+    match (lhs.type_check(types)?, rhs.type_check(types)?) {
+        (Some(lhs_type), Some(rhs_type)) if lhs_type != rhs_type => {
+            Err(TypeError::new(
+                format!("mismatched types {} and {}", lhs_type, rhs_type),
+                rhs.span().or_else(|| lhs.span()),
+            ))
+        },
+        (lhs_type, rhs_type) => Ok(lhs_type.or(rhs_type)),
+    }
+}
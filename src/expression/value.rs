@@ -0,0 +1,99 @@
+/// This module defines values expressions operate on.
+use std::fmt;
+
+use anyhow::Result;
+
+use super::number::Number;
+use super::typing::Type;
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum Value {
+    Bool(bool),
+    Number(Number),
+    String(String),
+}
+
+impl Value {
+    pub fn type_of(&self) -> Type {
+        match self {
+            Self::Bool(_) => Type::Bool,
+            Self::Number(_) => Type::Number,
+            Self::String(_) => Type::String,
+        }
+    }
+
+    pub fn as_bool(&self) -> Result<bool> {
+        match self {
+            Self::Bool(value) => Ok(*value),
+            _ => anyhow::bail!("Expected bool, found {}", self.type_of()),
+        }
+    }
+
+    pub fn as_number(&self) -> Result<Number> {
+        match self {
+            Self::Number(value) => Ok(*value),
+            _ => anyhow::bail!("Expected number, found {}", self.type_of()),
+        }
+    }
+}
+
+impl From<bool> for Value {
+    fn from(value: bool) -> Self {
+        Self::Bool(value)
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Bool(value) => write!(formatter, "{}", value),
+            Self::Number(value) => write!(formatter, "{}", value),
+            Self::String(value) => write!(formatter, "\"{}\"", value),
+        }
+    }
+}
+
+impl From<Number> for Value {
+    fn from(value: Number) -> Self {
+        Self::Number(value)
+    }
+}
+
+impl From<i64> for Value {
+    fn from(value: i64) -> Self {
+        Self::Number(value.into())
+    }
+}
+
+impl From<f64> for Value {
+    fn from(value: f64) -> Self {
+        Self::Number(value.into())
+    }
+}
+
+impl From<&str> for Value {
+    fn from(value: &str) -> Self {
+        Self::String(value.into())
+    }
+}
+
+impl From<String> for Value {
+    fn from(value: String) -> Self {
+        Self::String(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_conversions() {
+        assert_eq!(Number::Int(4), Value::from(4).as_number().unwrap());
+        assert_eq!(true, Value::Bool(true).as_bool().unwrap());
+        assert_eq!(
+            "Expected number, found string",
+            format!("{}", Value::from("DE").as_number().unwrap_err())
+        );
+    }
+}
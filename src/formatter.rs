@@ -0,0 +1,164 @@
+/// This module implements the `fmt` subcommand, which rewrites rule files
+/// in the canonical form, so that diffs of the rules show changes of the
+/// rules only.
+///
+/// A rule file holds a rule per line. Comments start with `#` and run to
+/// the end of the line, either after a rule or on a line of their own.
+/// They are kept as they are, as are single blank lines grouping the
+/// rules. Numbers are kept as written, so that `12.5%` stays `12.5%`.
+use std::fs;
+
+use anyhow::{Context as _, Result};
+
+use crate::expression::rule::Rule;
+
+const USAGE: &str = "Usage: expressions_evaluation fmt [--check] FILE...";
+
+/// Formats the contents of a rule file. Comments are kept, runs of blank
+/// lines are squeezed into one and the file ends with a single newline.
+pub fn format(source: &str) -> Result<String> {
+    let mut lines: Vec<String> = vec![];
+
+    for (index, line) in source.lines().enumerate() {
+        let (rule, comment) = split_comment(line);
+        let rule = rule.trim();
+
+        if rule.is_empty() && comment.is_none() {
+            if lines.last().map(String::is_empty) == Some(false) {
+                lines.push(String::new());
+            }
+
+            continue;
+        }
+
+        let mut formatted = match rule {
+            "" => String::new(),
+            rule => Rule::parse(rule)
+                .with_context(|| format!("Line {}", index + 1))?
+                .to_string(),
+        };
+
+        if let Some(comment) = comment {
+            if !formatted.is_empty() {
+                formatted.push(' ');
+            }
+            formatted.push_str(comment.trim_end());
+        }
+
+        lines.push(formatted);
+    }
+
+    if lines.last().map(String::is_empty) == Some(true) {
+        lines.pop();
+    }
+
+    Ok(lines.into_iter().map(|line| line + "\n").collect())
+}
+
+/// Splits the line into the rule and the comment, if any. A `#` within a
+/// string literal, such as `"#1"`, doesn't start a comment.
+fn split_comment(line: &str) -> (&str, Option<&str>) {
+    let mut quoted = false;
+
+    for (index, character) in line.char_indices() {
+        match character {
+            '"' => quoted = !quoted,
+            '#' if !quoted => return (&line[..index], Some(&line[index..])),
+            _ => {},
+        }
+    }
+
+    (line, None)
+}
+
+/// Runs the subcommand with the arguments following `fmt`, returning the
+/// exit code. With `--check`, files are left intact and the code is
+/// non-zero if any of them is not formatted.
+pub fn run(arguments: &[String]) -> i32 {
+    let check = arguments.iter().any(|argument| argument == "--check");
+    let paths: Vec<&String> = arguments
+        .iter()
+        .filter(|argument| *argument != "--check")
+        .collect();
+
+    if paths.is_empty() {
+        eprintln!("{}", USAGE);
+
+        return 2;
+    }
+
+    let mut code = 0;
+    for path in paths {
+        match format_file(path, check) {
+            Ok(true) => {},
+            Ok(false) => {
+                eprintln!("{} is not formatted", path);
+                code = 1;
+            },
+            Err(error) => {
+                eprintln!("{}: {:#}", path, error);
+                code = 1;
+            },
+        }
+    }
+
+    code
+}
+
+/// Formats the file, unless only checking it. Returns whether the file
+/// ends up formatted.
+fn format_file(path: &str, check: bool) -> Result<bool> {
+    let source = fs::read_to_string(path)?;
+    let formatted = format(&source)?;
+
+    if formatted == source {
+        return Ok(true);
+    }
+
+    if check {
+        return Ok(false);
+    }
+
+    fs::write(path, formatted)?;
+
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_formatting() {
+        let source = "\n# Labels\nA&&B and !C  => H = M\n\n\n\
+            ELSE => H = NONE  \n\n  # Rates\nconst RATE_M=(10)\n\
+            H = M|P=>K[2]=D+(D*E/RATE_M)\n\n";
+        let expected = "# Labels\nA && B && !C => H = M\n\n\
+            else => H = NONE\n\n# Rates\nconst RATE_M = 10\n\
+            H = M | P => K[2] = D + D * E / RATE_M\n";
+        let formatted = format(source).unwrap();
+
+        assert_eq!(expected, formatted);
+        assert_eq!(expected, format(&formatted).unwrap());
+    }
+
+    #[test]
+    fn test_literals_and_comments() {
+        let source = "H = M=>K=D * 12.5% + 1_000 + 0x1F  # fees\n\
+            plan == \"#1\"  => H = M #  top plan \n";
+        let expected = "H = M => K = D * 12.5% + 1_000 + 0x1F # fees\n\
+            plan == \"#1\" => H = M #  top plan\n";
+
+        assert_eq!(expected, format(source).unwrap());
+    }
+
+    #[test]
+    fn test_formatting_errors() {
+        let error = format("A => H = M\nA && => H = M").unwrap_err();
+
+        assert_eq!(
+            "Line 2: Unable to parse the expression A && => H = M",
+            format!("{:#}", error)
+        );
+    }
+}
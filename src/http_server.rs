@@ -5,26 +5,73 @@ mod error;
 use rocket::Rocket;
 use rocket_contrib::json::Json;
 
+use crate::expression::rule::{Rule, Source};
 use crate::solver::Solver;
 use self::error::Error;
 
-use request::Request;
-use response::Response;
+use request::{Conversion, PartialRequest, Request};
+use response::{Converted, PartialResponse, Response};
 
 
 #[post("/solution", data = "<request>")]
 fn solution(request: Json<Request>) -> Result<Json<Response>, Error> {
-    let mut solver = Solver::default();
-    let (bool_vars, float_vars) = (&request.variables).into();
+    let mut solver = Solver::default()
+        .with_arithmetic(request.arithmetic)
+        .with_float_policy(request.float_policy)
+        .with_precision(request.precision)
+        .with_optimization(request.optimize);
+    let context = (&request.variables).into();
+    solver.declare(&context);
     solver.add_all(&request.additional_rules)?;
 
-    let solution = solver.solve(bool_vars, float_vars)?;
+    let response = if request.cross_check {
+        solver.cross_check(&context)?.into()
+    } else {
+        solver.solve(&context)?.into()
+    };
 
-    Ok(Json(solution.into()))
+    Ok(Json(response))
+}
+
+#[post("/possibilities", data = "<request>")]
+fn possibilities(
+    request: Json<PartialRequest>,
+) -> Result<Json<PartialResponse>, Error> {
+    let mut solver = Solver::default().with_arithmetic(request.arithmetic);
+    let context = (&*request).into();
+    solver.declare(&context);
+    solver.add_all(&request.additional_rules)?;
+
+    Ok(Json(solver.solve_partially(&context)?.into()))
+}
+
+/// Converts rules in the text form to JSON ASTs.
+#[post("/ast", data = "<request>")]
+fn ast(request: Json<Conversion<String>>) -> Result<Json<Converted<Rule>>, Error> {
+    let rules = request
+        .rules
+        .iter()
+        .map(|rule| Rule::parse(rule))
+        .collect::<anyhow::Result<_>>()?;
+
+    Ok(Json(Converted { rules }))
+}
+
+/// Converts JSON ASTs to rules in the canonical text form. Rules in the
+/// text form are accepted as well, and are formatted.
+#[post("/text", data = "<request>")]
+fn text(request: Json<Conversion<Source>>) -> Result<Json<Converted<String>>, Error> {
+    let rules = request
+        .rules
+        .iter()
+        .map(|rule| rule.rule().map(|rule| rule.to_string()))
+        .collect::<anyhow::Result<_>>()?;
+
+    Ok(Json(Converted { rules }))
 }
 
 pub fn server() -> Rocket {
-    rocket::ignite().mount("/", routes![solution])
+    rocket::ignite().mount("/", routes![solution, possibilities, ast, text])
 }
 
 #[cfg(test)]
@@ -36,9 +83,12 @@ mod test {
     // A convinience macro to make a request with the specified body fixture.
     macro_rules! make_request {
         ($var:ident, $str:expr) => {
+            make_request!($var, "/solution", $str);
+        };
+        ($var:ident, $path:expr, $str:expr) => {
             let client = Client::new(server()).expect("valid rocket instance");
             let mut $var = client
-                .post("/solution")
+                .post($path)
                 .header(ContentType::JSON)
                 .body(include_str!(concat!("./http_server/test_fixtures/", $str, ".json")))
                 .dispatch();
@@ -53,6 +103,94 @@ mod test {
         assert_eq!(response.body_string(), Some(r#"{"H":"P","K":1.05}"#.into()));
     }
 
+    #[test]
+    fn test_decimal_arithmetic() {
+        make_request!(response, "decimal");
+
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!(response.body_string(), Some(r#"{"H":"M","K":"25.99"}"#.into()));
+    }
+
+    #[test]
+    fn test_cross_check() {
+        make_request!(response, "cross_check");
+        let body =
+            r#"{"H":"P","K":0.30000000000000004,"EXACT":{"K":"3/10"},"DEVIATION":4e-17}"#;
+
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!(response.body_string(), Some(body.into()));
+    }
+
+    #[test]
+    fn test_precision() {
+        make_request!(response, "precision");
+
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!(response.body_string(), Some(r#"{"H":"P","K":0.3}"#.into()));
+    }
+
+    #[test]
+    fn test_multiple_outputs() {
+        make_request!(response, "multiple_outputs");
+        let body = r#"{"H":"P","net":30,"tax":5.7,"gross":35.7}"#;
+
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!(response.body_string(), Some(body.into()));
+    }
+
+    #[test]
+    fn test_division_by_zero() {
+        make_request!(response, "division_by_zero");
+        let error_text = r#"{"reason":"Division by zero in D / (E - F) where D = 1.05, E = 1, F = 1"}"#;
+
+        assert_eq!(response.status(), Status::UnprocessableEntity);
+        assert_eq!(response.body_string(), Some(error_text.into()));
+    }
+
+    #[test]
+    fn test_default_rule() {
+        make_request!(response, "default_rule");
+
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!(response.body_string(), Some(r#"{"H":"NONE","K":0,"DEFAULT":true}"#.into()));
+    }
+
+    #[test]
+    fn test_possibilities() {
+        make_request!(response, "/possibilities", "partially_known");
+
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!(response.body_string(), Some(r#"{"POSSIBLE":["T","P"]}"#.into()));
+    }
+
+    #[test]
+    fn test_ast_rules() {
+        make_request!(response, "ast_rules");
+
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!(response.body_string(), Some(r#"{"H":"M","K":2.1}"#.into()));
+    }
+
+    #[test]
+    fn test_conversions() {
+        make_request!(response, "/ast", "text_conversion");
+        let ast = response.body_string().unwrap();
+
+        assert_eq!(response.status(), Status::Ok);
+        assert!(ast.starts_with(r#"{"rules":[{"version":1,"rule":"boolean""#));
+
+        let client = Client::new(server()).expect("valid rocket instance");
+        let mut response = client
+            .post("/text")
+            .header(ContentType::JSON)
+            .body(ast)
+            .dispatch();
+        let body = r#"{"rules":["A && !(B || C) => H = M","H = M => K[2] = D * 2"]}"#;
+
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!(response.body_string(), Some(body.into()));
+    }
+
     #[test]
     fn test_unknown_variable_supplied() {
         make_request!(response, "unknown_variable");
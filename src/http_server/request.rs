@@ -1,12 +1,65 @@
 use std::collections::HashMap;
 
 use serde::Deserialize;
+use serde_json::Value as Json;
+
+use crate::expression::rule::Source;
+use crate::expression::{Arithmetic, Context, FloatPolicy, Precision, Value};
 
 /// Represents HTTP body request JSON.
 #[derive(Deserialize)]
 pub struct Request {
-    pub additional_rules: Vec<String>,
+    /// Rules in the text form or as JSON ASTs, see `rule::Source`.
+    pub additional_rules: Vec<Source>,
     pub variables: Variables,
+    /// How numbers are evaluated, e.g. `{"mode": "decimal", "scale": 2,
+    /// "rounding": "half_even"}`. Floats are used by default.
+    #[serde(default)]
+    pub arithmetic: Arithmetic,
+    /// Whether to report the exact result and how far K deviates from it.
+    #[serde(default)]
+    pub cross_check: bool,
+    /// What to do about divisions by zero and infinite results, following
+    /// IEEE 754 by default. JSON has no infinities, so clients would rather
+    /// have them reported as errors.
+    #[serde(default)]
+    pub float_policy: FloatPolicy,
+    /// The precision K is rounded to, e.g. `{"digits": 2, "rounding":
+    /// "half_up"}`, unless the matching rule declares its own.
+    #[serde(default)]
+    pub precision: Option<Precision>,
+    /// Whether the rules are optimized before they are evaluated.
+    #[serde(default)]
+    pub optimize: bool,
+}
+
+/// Represents HTTP body request JSON of a partially known case, where any
+/// variable may be missing.
+#[derive(Deserialize)]
+pub struct PartialRequest {
+    pub additional_rules: Vec<Source>,
+    pub variables: HashMap<String, Json>,
+    #[serde(default)]
+    pub arithmetic: Arithmetic,
+}
+
+impl From<&PartialRequest> for Context {
+    fn from(request: &PartialRequest) -> Self {
+        let mut context = Context::default();
+
+        for (name, value) in &request.variables {
+            insert_variable(name.clone(), value, &mut context);
+        }
+
+        context
+    }
+}
+
+/// Represents HTTP body request JSON of a conversion of rules between the
+/// text form and JSON ASTs.
+#[derive(Deserialize)]
+pub struct Conversion<T> {
+    pub rules: Vec<T>,
 }
 
 #[derive(Deserialize)]
@@ -18,26 +71,58 @@ pub struct Variables {
     d: f64,
     e: i64,
     f: i64,
+    /// Any other variables. Nested objects are flattened, so their fields
+    /// are addressed with dotted paths, such as `customer.age`.
+    #[serde(flatten)]
+    other: HashMap<String, Json>,
 }
 
 // Solver is generic, so we need an ad-hoc converter to adhere to
 // solver's params.
-impl From<&Variables> for (HashMap<String, bool>, HashMap<String, f64>) {
+impl From<&Variables> for Context {
     fn from(variables: &Variables) -> Self {
-        let mut bool_vars = HashMap::new();
-        bool_vars.insert("A".into(), variables.a);
-        bool_vars.insert("B".into(), variables.b);
-        bool_vars.insert("C".into(), variables.c);
+        let mut context = Context::default();
+        let table = &mut context.variables;
+        table.insert("A".into(), variables.a.into());
+        table.insert("B".into(), variables.b.into());
+        table.insert("C".into(), variables.c.into());
+        table.insert("D".into(), variables.d.into());
+        table.insert("E".into(), variables.e.into());
+        table.insert("F".into(), variables.f.into());
 
-        let mut arithmetic_vars = HashMap::new();
-        arithmetic_vars.insert("D".into(), variables.d);
-        arithmetic_vars.insert("E".into(), variables.e as _);
-        arithmetic_vars.insert("F".into(), variables.f as _);
+        for (name, value) in &variables.other {
+            insert_variable(name.clone(), value, &mut context);
+        }
 
-        (bool_vars, arithmetic_vars)
+        context
     }
 }
 
+fn insert_variable(path: String, value: &Json, context: &mut Context) {
+    let value = match value {
+        Json::Bool(value) => Value::Bool(*value),
+        Json::String(value) => Value::String(value.clone()),
+        Json::Number(value) => match (value.as_i64(), value.as_f64()) {
+            (Some(value), _) => value.into(),
+            (None, Some(value)) => value.into(),
+            (None, None) => return,
+        },
+        Json::Object(fields) => {
+            for (name, value) in fields {
+                let path = format!("{}.{}", path, name);
+
+                insert_variable(path, value, context);
+            }
+
+            return;
+        },
+        // Expressions can't refer to these, so they are left out.
+        Json::Null | Json::Array(_) => return,
+    };
+
+    context.variables.insert(path, value);
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -62,7 +147,58 @@ mod test {
 
         let request: Request = serde_json::from_str(data).unwrap();
 
-        assert_eq!(request.additional_rules, vec!["A && B => H = P"]);
+        assert_eq!(
+            request.additional_rules,
+            vec![Source::Text("A && B => H = P".into())]
+        );
         assert_eq!(request.variables.c, false);
+        assert_eq!(request.float_policy, FloatPolicy::default());
+    }
+
+    #[test]
+    fn test_nested_variables() {
+        let data = r#"
+            {
+              "A": true,
+              "B": true,
+              "C": false,
+              "D": 1.05,
+              "E": 4,
+              "F": 42,
+              "customer": {
+                "age": 42,
+                "country": "DE",
+                "is_vip": true,
+                "address": { "zip_code": 10115 }
+              }
+            }
+        "#;
+
+        let variables: Variables = serde_json::from_str(data).unwrap();
+        let context: Context = (&variables).into();
+
+        let variables = &context.variables;
+
+        assert_eq!(variables["customer.is_vip"], Value::Bool(true));
+        assert_eq!(variables["customer.age"], 42.into());
+        assert_eq!(variables["customer.address.zip_code"], 10115.into());
+        assert_eq!(variables["E"], 4.into());
+        assert_eq!(variables["customer.country"], "DE".into());
+    }
+
+    #[test]
+    fn test_partial_request_deserialization() {
+        let data = r#"
+            {
+              "variables": { "B": true, "customer": { "age": 42 } },
+              "additional_rules": []
+            }
+        "#;
+
+        let request: PartialRequest = serde_json::from_str(data).unwrap();
+        let context: Context = (&request).into();
+
+        assert_eq!(2, context.variables.len());
+        assert_eq!(context.variables["customer.age"], 42.into());
     }
 }
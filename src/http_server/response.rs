@@ -1,17 +1,95 @@
+use serde::ser::{SerializeMap, Serializer};
 use serde::Serialize;
 
+use crate::expression::Number;
+use crate::solver::{CrossCheck, Possibilities, Solution};
+
 // Represenets HTTP body response JSON
 #[derive(Serialize)]
 #[serde(rename_all = "UPPERCASE")]
 pub struct Response {
     h: String,
-    k: f64,
+    /// K, along with any other output, as keys of their own. Outputs can't
+    /// be named after the other keys, see `arithmetic::RESERVED_OUTPUTS`.
+    #[serde(flatten)]
+    outputs: Outputs<Number>,
+    /// Only present when the default rule was used.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    default: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    exact: Option<Outputs<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    deviation: Option<f64>,
 }
 
 // Convert solver's result to response
-impl From<(String, f64)> for Response {
-    fn from((h, k): (String, f64)) -> Self {
-        Self { h, k }
+impl From<Solution> for Response {
+    fn from(solution: Solution) -> Self {
+        Self {
+            h: solution.label,
+            outputs: Outputs(solution.outputs),
+            default: solution.default,
+            exact: None,
+            deviation: None,
+        }
+    }
+}
+
+impl From<CrossCheck> for Response {
+    fn from(check: CrossCheck) -> Self {
+        Self {
+            h: check.label,
+            outputs: Outputs(check.approximate),
+            default: check.default,
+            exact: Some(Outputs(
+                check
+                    .exact
+                    .into_iter()
+                    .map(|(name, value)| (name, value.to_string()))
+                    .collect(),
+            )),
+            deviation: Some(check.deviation),
+        }
+    }
+}
+
+/// Represents HTTP body response JSON of converted rules, in the order of
+/// the request.
+#[derive(Serialize)]
+pub struct Converted<T> {
+    pub rules: Vec<T>,
+}
+
+/// Represents HTTP body response JSON of a partially known case.
+#[derive(Serialize)]
+#[serde(rename_all = "UPPERCASE")]
+pub struct PartialResponse {
+    possible: Vec<String>,
+    /// Only present when a single label is possible.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    certain: Option<String>,
+}
+
+impl From<Possibilities> for PartialResponse {
+    fn from(possibilities: Possibilities) -> Self {
+        Self {
+            possible: possibilities.possible,
+            certain: possibilities.certain,
+        }
+    }
+}
+
+/// Outputs of a formula, serialized as a map in the order of assignment.
+pub struct Outputs<T>(Vec<(String, T)>);
+
+impl<T: Serialize> Serialize for Outputs<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(self.0.len()))?;
+        for (name, value) in &self.0 {
+            map.serialize_entry(name, value)?;
+        }
+
+        map.end()
     }
 }
 
@@ -19,13 +97,67 @@ impl From<(String, f64)> for Response {
 mod test {
     use super::*;
 
+    fn solution(label: &str, value: Number) -> Solution {
+        Solution {
+            label: label.into(),
+            outputs: vec![("K".into(), value)],
+            default: false,
+        }
+    }
+
     #[test]
     fn test_response_serialization() {
         let expected = r#"{"H":"M","K":0.133333}"#;
 
-        let response: Response = ("M".into(), 0.133333).into();
+        let response: Response = solution("M", 0.133333.into()).into();
         let actual = serde_json::to_string(&response).unwrap();
 
         assert_eq!(expected, actual);
+
+        let k = 9_007_199_254_740_993.into();
+        let response: Response = solution("M", k).into();
+        let actual = serde_json::to_string(&response).unwrap();
+
+        assert_eq!(r#"{"H":"M","K":9007199254740993}"#, actual);
+    }
+
+    #[test]
+    fn test_default_serialization() {
+        let response: Response = Solution {
+            default: true,
+            ..solution("NONE", 0.into())
+        }
+        .into();
+        let actual = serde_json::to_string(&response).unwrap();
+
+        assert_eq!(r#"{"H":"NONE","K":0,"DEFAULT":true}"#, actual);
+    }
+
+    #[test]
+    fn test_outputs_serialization() {
+        let response: Response = Solution {
+            label: "P".into(),
+            outputs: vec![
+                ("net".into(), 4.5.into()),
+                ("tax".into(), 0.86.into()),
+            ],
+            default: false,
+        }
+        .into();
+        let actual = serde_json::to_string(&response).unwrap();
+
+        assert_eq!(r#"{"H":"P","net":4.5,"tax":0.86}"#, actual);
+    }
+
+    #[test]
+    fn test_partial_serialization() {
+        let response: PartialResponse = Possibilities {
+            possible: vec!["M".into()],
+            certain: Some("M".into()),
+        }
+        .into();
+        let actual = serde_json::to_string(&response).unwrap();
+
+        assert_eq!(r#"{"POSSIBLE":["M"],"CERTAIN":"M"}"#, actual);
     }
 }
@@ -0,0 +1,6 @@
+#![feature(try_find)]
+extern crate anyhow;
+extern crate nom;
+
+pub mod expression;
+pub mod solver;
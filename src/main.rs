@@ -1,14 +1,22 @@
-#![feature(try_find, decl_macro)]
+#![feature(decl_macro)]
 extern crate anyhow;
-extern crate nom;
 #[macro_use]
 extern crate rocket;
 extern crate rocket_contrib;
 
-mod expression;
+use std::env;
+use std::process;
+
+use expressions_evaluation::{expression, solver};
+
+mod formatter;
 mod http_server;
-mod solver;
 
 fn main() {
-    http_server::server().launch();
+    let arguments: Vec<String> = env::args().skip(1).collect();
+
+    match arguments.first().map(String::as_str) {
+        Some("fmt") => process::exit(formatter::run(&arguments[1..])),
+        _ => http_server::server().launch(),
+    };
 }
@@ -1,14 +1,34 @@
 use std::collections::HashMap;
 
-use anyhow::{Context, Result};
+use anyhow::{Context as _, Result};
 
-use crate::expression::boolean::parse as parse_boolean_expression;
 use crate::expression::boolean::Expression as BoolExpression;
 
-use crate::expression::arithmetic::parse as parse_arithmetic_expression;
-use crate::expression::arithmetic::Expression as ArithmeticExpression;
+use crate::expression::arithmetic::{Formula, Matcher, Residual};
 
-use crate::expression::Reducible;
+use crate::expression::optimize::Optimize;
+use crate::expression::rule::{Rule, Source};
+use crate::expression::typing::{expect, Type, TypeCheck, TypeError, Types};
+use crate::expression::{
+    Arithmetic, Context, FloatPolicy, Number, PartiallyReducible, Precision,
+    Rational, Reducible, Truth, Value,
+};
+
+const BASE_CONSTANTS: [&str; 3] = [
+    "const RATE_M = 10",
+    "const RATE_P = 25.5",
+    "const RATE_T = 30",
+];
+
+/// The base variables every request provides.
+const BASE_TYPES: [(&str, Type); 6] = [
+    ("A", Type::Bool),
+    ("B", Type::Bool),
+    ("C", Type::Bool),
+    ("D", Type::Number),
+    ("E", Type::Number),
+    ("F", Type::Number),
+];
 
 const BASE_BOOLEAN: [&str; 3] = [
     "A && B && !C => H = M",
@@ -17,147 +37,1072 @@ const BASE_BOOLEAN: [&str; 3] = [
 ];
 
 const BASE_ARITHMETIC: [&str; 3] = [
-    "H = M => K = D + (D * E / 10)",
-    "H = P => K = D + (D * (E - F) / 25.5)",
-    "H = T => K = D - (D * F / 30)",
+    "H = M => K = D + (D * E / RATE_M)",
+    "H = P => K = D + (D * (E - F) / RATE_P)",
+    "H = T => K = D - (D * F / RATE_T)",
 ];
 
+/// The label of the first truthy boolean expression and the results of the
+/// matching arithmetic expression.
+#[derive(PartialEq, Debug)]
+pub struct Solution {
+    pub label: String,
+    /// The outputs of the formula in the order of assignment, usually just
+    /// K.
+    pub outputs: Vec<(String, Number)>,
+    /// Whether no boolean expression was truthy, so that the label comes
+    /// from the default rule.
+    pub default: bool,
+}
+
+/// The result of the stored expressions evaluated both exactly and using
+/// the solver's arithmetic.
+#[derive(Debug)]
+pub struct CrossCheck {
+    pub label: String,
+    pub default: bool,
+    pub exact: Vec<(String, Rational)>,
+    pub approximate: Vec<(String, Number)>,
+    /// The largest absolute difference between the two results of an
+    /// output.
+    pub deviation: f64,
+}
+
+/// Labels a partially known context may still lead to.
+#[derive(PartialEq, Debug)]
+pub struct Possibilities {
+    /// Labels which are still possible, in the order of precedence.
+    pub possible: Vec<String>,
+    /// The label, if it no longer depends on the missing variables.
+    pub certain: Option<String>,
+}
+
+/// Boolean and arithmetic rules, in the order they were added.
+#[derive(Clone, Default)]
+struct Rules {
+    boolean: Vec<(BoolExpression, String)>,
+    arithmetic: Vec<Formula>,
+}
+
+impl Optimize for Rules {
+    fn optimize(self, context: &Context) -> Self {
+        let boolean = self.boolean.into_iter();
+        let arithmetic = self.arithmetic.into_iter();
+
+        Self {
+            boolean: boolean
+                .map(|(expression, label)| {
+                    (expression.optimize(context), label)
+                })
+                .collect(),
+            arithmetic: arithmetic
+                .map(|formula| formula.optimize(context))
+                .collect(),
+        }
+    }
+}
+
 /// The backbone of the application. Solves boolean & arithmetic
 /// expressions.
 pub struct Solver {
-    boolean: Vec<(BoolExpression, String)>,
-    arithmetic: Vec<(ArithmeticExpression, String)>,
+    /// The rules as added.
+    rules: Rules,
+    /// The rules once optimized, if the solver is told to optimize them,
+    /// see `Solver::with_optimization`.
+    optimized: Option<Rules>,
+    /// The label of the `else => H = ...` rule, if any.
+    default: Option<String>,
+    types: Types,
+    constants: HashMap<String, Value>,
+    numbers: Arithmetic,
+    floats: FloatPolicy,
+    precision: Option<Precision>,
 }
 
 impl Solver {
-    /// Try to parse the given expression as either boolean or arithmetic.
+    /// Parses the given rule, see `Rule::parse`, and adds it. Type errors
+    /// point at the offending part of the rule.
     pub fn add(&mut self, input: &str) -> Result<()> {
-        let Self {
-            boolean,
-            arithmetic,
-        } = self;
+        self.insert(Rule::parse(input)?, Some(input))
+    }
 
-        if let Ok((_, result)) = parse_boolean_expression(input) {
-            return Ok(boolean.push(result));
-        }
+    /// Adds a rule which isn't written down, such as one built in code,
+    /// see `expression::builder`, or given as a JSON AST.
+    pub fn add_rule(&mut self, rule: Rule) -> Result<()> {
+        self.insert(rule, None)
+    }
 
-        if let Ok((_, result)) = parse_arithmetic_expression(input) {
-            return Ok(arithmetic.push(result));
+    /// Adds the rules, given either in the text form or as JSON ASTs, see
+    /// `rule::Source`.
+    pub fn add_all(&mut self, rules: &[Source]) -> Result<()> {
+        for rule in rules {
+            match rule {
+                Source::Text(text) => self.add(text)?,
+                Source::Ast(_) => self.add_rule(rule.rule()?)?,
+            }
         }
 
-        anyhow::bail!("Unable to parse the expression {}", input)
+        Ok(())
     }
 
-    pub fn add_all(&mut self, expressions: &Vec<String>) -> Result<()> {
-        for expression in expressions {
-            self.add(&expression)?;
+    /// Adds the rule. A later default rule or precision directive replaces
+    /// the earlier one, as well as the precision the solver is made with.
+    ///
+    /// The rule is type checked against the base variables A to F and the
+    /// declared ones, see `Solver::declare`.
+    ///
+    /// Constants are evaluated right away, so they may only refer to the
+    /// constants added before them. Variables of the request shadow the
+    /// constants of the same name, whereas constants shadow nothing but
+    /// the constants they redefine.
+    ///
+    /// Once type checked, the rule is optimized, if the solver is told to,
+    /// see `Solver::with_optimization`.
+    fn insert(&mut self, rule: Rule, source: Option<&str>) -> Result<()> {
+        let locate = |error: TypeError| match source {
+            Some(source) => error.locate(source),
+            None => error.explain(),
+        };
+        let folding = &self.context(&Context::default());
+        let types = &mut self.types;
+
+        match rule {
+            Rule::Default(label) => self.default = Some(label),
+            Rule::Precision(precision) => self.precision = Some(precision),
+            Rule::Constant(name, expression) => {
+                expression.type_check(types).map_err(locate)?;
+
+                let context = Context {
+                    constants: self.constants.clone(),
+                    ..Context::default()
+                };
+                let value: Value = expression.reduce(&context)?;
+
+                // Declared variables keep their types, since they shadow the
+                // constant anyway.
+                types.entry(name.clone()).or_insert_with(|| value.type_of());
+
+                self.constants.insert(name, value);
+            },
+            Rule::Boolean(expression, label) => {
+                expression.type_check(types).map_err(locate)?;
+
+                if let Some(optimized) = &mut self.optimized {
+                    let optimal = expression.clone().optimize(folding);
+                    optimized.boolean.push((optimal, label.clone()));
+                }
+
+                self.rules.boolean.push((expression, label));
+            },
+            Rule::Arithmetic(formula) => {
+                if let Some(guard) = &formula.guard {
+                    expect(guard, Type::Bool, types).map_err(locate)?;
+                }
+
+                // Later outputs may refer to the earlier ones.
+                let mut scope = types.clone();
+                for output in &formula.outputs {
+                    expect(&output.expression, Type::Number, &scope)
+                        .map_err(locate)?;
+
+                    scope.insert(output.name.clone(), Type::Number);
+                }
+
+                if let Some(optimized) = &mut self.optimized {
+                    optimized
+                        .arithmetic
+                        .push(formula.clone().optimize(folding));
+                }
+
+                self.rules.arithmetic.push(formula);
+            },
         }
 
         Ok(())
     }
 
-    /// Given variable tables, solve stored expressions.
-    pub fn solve(
+    /// Declares types of the variables in the context. Expressions added
+    /// afterwards may only use the variables according to their types.
+    pub fn declare(&mut self, context: &Context) {
+        for (name, value) in &context.variables {
+            self.types.insert(name.clone(), value.type_of());
+        }
+    }
+
+    /// Makes the solver evaluate numbers using the given arithmetic, such
+    /// as decimals for monetary formulas.
+    pub fn with_arithmetic(mut self, arithmetic: Arithmetic) -> Self {
+        self.numbers = arithmetic;
+
+        self.reoptimize()
+    }
+
+    /// Makes the solver report divisions by zero, or infinite results,
+    /// as errors instead of following IEEE 754.
+    pub fn with_float_policy(mut self, floats: FloatPolicy) -> Self {
+        self.floats = floats;
+
+        self.reoptimize()
+    }
+
+    /// Makes the solver round results of rules which don't declare their
+    /// own precision.
+    pub fn with_precision(mut self, precision: Option<Precision>) -> Self {
+        self.precision = precision;
+
+        self
+    }
+
+    /// Makes the solver optimize its rules, folding constants and dropping
+    /// identities, such as `D * 1` or `A && true`. The rules added before
+    /// are optimized as well as the ones added afterwards.
+    pub fn with_optimization(mut self, optimize: bool) -> Self {
+        self.optimized = if optimize {
+            Some(Rules::default())
+        } else {
+            None
+        };
+
+        self.reoptimize()
+    }
+
+    /// Optimizes the rules anew, if the solver is told to optimize them.
+    /// Constants are folded the way the solver evaluates them, so that the
+    /// optimized rules evaluate to the same results whatever the order of
+    /// adding rules and configuring the solver.
+    fn reoptimize(mut self) -> Self {
+        if self.optimized.is_some() {
+            let folding = self.context(&Context::default());
+
+            self.optimized = Some(self.rules.clone().optimize(&folding));
+        }
+
+        self
+    }
+
+    /// The rules to evaluate, optimized ones if the solver is told to.
+    fn rules(&self) -> &Rules {
+        self.optimized.as_ref().unwrap_or(&self.rules)
+    }
+
+    /// Given variable tables, solve stored expressions. Each output is
+    /// rounded to its precision, or else the solver's one, before the
+    /// outputs after it see it.
+    pub fn solve(&self, context: &Context) -> Result<Solution> {
+        let mut scope = self.context(context);
+        let (label, default) = self.label(&scope)?;
+        let formula = self.formula(label, &scope)?;
+        let mut outputs = vec![];
+
+        for output in &formula.outputs {
+            let value: Value = output.expression.reduce(&scope)?;
+            let value = match output.precision.or(self.precision) {
+                Some(precision) => value.as_number()?.round(precision)?,
+                None => value.as_number()?,
+            };
+
+            scope.variables.insert(output.name.clone(), value.into());
+            outputs.push((output.name.clone(), value));
+        }
+
+        Ok(Solution {
+            label: label.into(),
+            outputs,
+            default,
+        })
+    }
+
+    /// Given variable tables, solve stored expressions without any
+    /// rounding. Conditions are still evaluated using the solver's
+    /// arithmetic.
+    pub fn solve_exact(
+        &self,
+        context: &Context,
+    ) -> Result<(String, Vec<(String, Rational)>)> {
+        let mut scope = self.context(context);
+        let (label, _) = self.label(&scope)?;
+        let formula = self.formula(label, &scope)?;
+        let mut outputs = vec![];
+
+        for output in &formula.outputs {
+            let value: Rational = output.expression.reduce(&scope)?;
+
+            scope.bind_exact(&output.name, value.clone());
+            outputs.push((output.name.clone(), value));
+        }
+
+        Ok((label.into(), outputs))
+    }
+
+    /// Given variable tables lacking some of the variables, substitute the
+    /// known ones into the outputs and fold the constants, leaving formulas
+    /// of the missing variables, such as `2 + 0.2 * E`. Outputs refer to
+    /// the earlier ones by name, unless they are known. Nothing is rounded,
+    /// as the formulas aren't final.
+    ///
+    /// Conditions of the boolean expressions must be known.
+    pub fn solve_residual(
         &self,
-        bool_vars: HashMap<String, bool>,
-        float_vars: HashMap<String, f64>,
-    ) -> Result<(String, f64)> {
-        // First, find a truthy boolean expression
-        let (_, label) = self
+        context: &Context,
+    ) -> Result<(String, Vec<(String, Residual)>)> {
+        let mut scope = self.context(context);
+        let (label, _) = self.label(&scope)?;
+        let formula = self.formula(label, &scope)?;
+        let mut outputs = vec![];
+
+        for output in &formula.outputs {
+            let residual: Residual = output.expression.reduce(&scope)?;
+
+            match &residual {
+                Residual::Known(value) => {
+                    scope.variables.insert(output.name.clone(), value.clone());
+                },
+                Residual::Formula(_) => {
+                    scope.variables.remove(&output.name);
+                    scope.constants.remove(&output.name);
+                },
+            }
+
+            outputs.push((output.name.clone(), residual));
+        }
+
+        Ok((label.into(), outputs))
+    }
+
+    /// Solves stored expressions both ways, to see how far the solver's
+    /// arithmetic, rounding included, is from the exact result.
+    pub fn cross_check(&self, context: &Context) -> Result<CrossCheck> {
+        let solution = self.solve(context)?;
+        let (_, exact) = self.solve_exact(context)?;
+        let mut deviation: f64 = 0.0;
+
+        for ((_, approximate), (_, exact)) in
+            solution.outputs.iter().zip(&exact)
+        {
+            let difference = Rational::from_number(*approximate)?
+                .subtract(exact)?
+                .abs()
+                .to_f64();
+
+            deviation = deviation.max(difference);
+        }
+
+        Ok(CrossCheck {
+            label: solution.label,
+            default: solution.default,
+            exact,
+            approximate: solution.outputs,
+            deviation,
+        })
+    }
+
+    /// Given variable tables lacking some of the variables, find the labels
+    /// which are still possible. Conditions are evaluated under Kleene
+    /// logic, where missing boolean variables, and comparisons involving
+    /// missing variables, are unknown.
+    pub fn solve_partially(&self, context: &Context) -> Result<Possibilities> {
+        let context = &self.context(context);
+        let mut possible: Vec<String> = vec![];
+        let mut decided = false;
+
+        for (expression, label) in self.rules().boolean.iter().rev() {
+            let truth = expression.reduce_partially(context)?;
+
+            if truth != Truth::False && !possible.contains(label) {
+                possible.push(label.clone());
+            }
+
+            // Rules of lower precedence can't apply anymore.
+            if truth == Truth::True {
+                decided = true;
+                break;
+            }
+        }
+
+        if let (false, Some(label)) = (decided, &self.default) {
+            if !possible.contains(label) {
+                possible.push(label.clone());
+            }
+        }
+
+        anyhow::ensure!(!possible.is_empty(), "Unable to find the solution");
+
+        let certain = match possible.as_slice() {
+            [label] => Some(label.clone()),
+            _ => None,
+        };
+
+        Ok(Possibilities { possible, certain })
+    }
+
+    /// Evaluation context with the solver's settings applied.
+    fn context(&self, context: &Context) -> Context {
+        Context {
+            constants: self.constants.clone(),
+            arithmetic: self.numbers,
+            floats: self.floats,
+            ..context.clone()
+        }
+    }
+
+    /// Finds the label of the first truthy boolean expression, or else the
+    /// label of the default rule. Tells whether the latter was used.
+    fn label(&self, context: &Context) -> Result<(&String, bool)> {
+        let truthy = self
+            .rules()
             .boolean
             .iter()
             .rev()
-            .try_find(|(expression, _)| expression.reduce(&bool_vars))?
-            .context("Unable to find the solution")?;
+            .try_find(|(expression, _)| expression.reduce(context))?;
 
-        // Then, find a matching float expression
-        let (expression, _) = self
-            .arithmetic
-            .iter()
-            .rev()
-            .find(|(_, float_label)| label == float_label)
-            .context("Unable to find the solution")?;
+        match (truthy, &self.default) {
+            (Some((_, label)), _) => Ok((label, false)),
+            (None, Some(label)) => Ok((label, true)),
+            (None, None) => anyhow::bail!("Unable to find the solution"),
+        }
+    }
+
+    /// Finds the arithmetic expression matching the label, whose guard, if
+    /// any, passes. Formulas naming the label take precedence over
+    /// wildcard ones, and newer formulas over older ones.
+    fn formula(&self, label: &str, context: &Context) -> Result<&Formula> {
+        let applies = |formula: &&Formula| match &formula.guard {
+            Some(guard) => guard.reduce(context),
+            None => Ok(true),
+        };
+        let formulas = self.rules().arithmetic.iter().rev();
+        let mut specific = formulas.clone().filter(|formula| {
+            formula.matcher != Matcher::Wildcard
+                && formula.matcher.matches(label)
+        });
+        let mut wildcards =
+            formulas.filter(|formula| formula.matcher == Matcher::Wildcard);
 
-        Ok((label.into(), expression.reduce(&float_vars)?))
+        match specific.try_find(applies)? {
+            Some(formula) => Ok(formula),
+            None => wildcards
+                .try_find(applies)?
+                .context("Unable to find the solution"),
+        }
     }
 }
 
 impl Default for Solver {
     fn default() -> Self {
-        let boolean = BASE_BOOLEAN.iter().fold(vec![], |mut acc, string| {
-            // Unwrap is justified, because our base expressions are okay.
-            let result = parse_boolean_expression(string).unwrap().1;
-            acc.push(result);
+        let mut solver = Self {
+            rules: Rules::default(),
+            optimized: None,
+            default: None,
+            types: BASE_TYPES
+                .iter()
+                .map(|&(name, type_)| (name.into(), type_))
+                .collect(),
+            constants: HashMap::new(),
+            numbers: Arithmetic::default(),
+            floats: FloatPolicy::default(),
+            precision: None,
+        };
 
-            acc
-        });
-
-        let arithmetic =
-            BASE_ARITHMETIC.iter().fold(vec![], |mut acc, string| {
-                // Unwrap is justified, because our base expressions are okay.
-                let result = parse_arithmetic_expression(string).unwrap().1;
-                acc.push(result);
-
-                acc
-            });
-
-        Self {
-            boolean,
-            arithmetic,
+        let base = BASE_CONSTANTS.iter().chain(&BASE_BOOLEAN);
+        for rule in base.chain(&BASE_ARITHMETIC) {
+            // Unwrap is justified, because our base expressions are okay.
+            solver.add(rule).unwrap();
         }
+
+        solver
     }
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::expression::Rounding;
+
+    fn variable_tables() -> Context {
+        let mut context = Context::default();
+        context.variables.insert("A".into(), false.into());
+        context.variables.insert("B".into(), true.into());
+        context.variables.insert("C".into(), true.into());
+
+        context.variables.insert("D".into(), 1.5.into());
+        context.variables.insert("E".into(), 20.0.into());
+        context.variables.insert("F".into(), 10.0.into());
 
-    fn variable_tables() -> (HashMap<String, bool>, HashMap<String, f64>) {
-        let mut bool_vars = HashMap::new();
-        bool_vars.insert("A".into(), false);
-        bool_vars.insert("B".into(), true);
-        bool_vars.insert("C".into(), true);
+        context
+    }
 
-        let mut arithmetic_vars = HashMap::new();
-        arithmetic_vars.insert("D".into(), 1.5);
-        arithmetic_vars.insert("E".into(), 20.0);
-        arithmetic_vars.insert("F".into(), 10.0);
+    /// The label and K of the solution.
+    fn solve(solver: &Solver, context: &Context) -> (String, Number) {
+        let solution = solver.solve(context).unwrap();
+        let (_, value) = solution
+            .outputs
+            .into_iter()
+            .find(|(name, _)| name == "K")
+            .unwrap();
 
-        (bool_vars, arithmetic_vars)
+        (solution.label, value)
     }
 
     #[test]
     fn test_default_solver() {
-        let (bool_vars, arithmetic_vars) = variable_tables();
+        let context = variable_tables();
         let solver = Solver::default();
-        let solution = solver.solve(bool_vars, arithmetic_vars).unwrap();
+        let solution = solve(&solver, &context);
 
-        assert_eq!(("T".into(), 1.0), solution);
+        assert_eq!(("T".into(), 1.0.into()), solution);
     }
 
     // Newly added values take precedence over the basic ones.
     #[test]
     fn test_adding_expressions() {
-        let (bool_vars, arithmetic_vars) = variable_tables();
+        let context = variable_tables();
         let mut solver = Solver::default();
 
         solver
             .add("H = M => K = (E * D * D / (E * (D * (F + E))))")
             .unwrap();
         solver.add("A || !A => H = M").unwrap();
-        let solution = solver.solve(bool_vars, arithmetic_vars).unwrap();
+        let solution = solve(&solver, &context);
+
+        assert_eq!(("M".into(), 0.05.into()), solution);
+    }
+
+    #[test]
+    fn test_built_rules() {
+        use crate::expression::builder::{label, not, var, Condition};
+
+        let context = variable_tables();
+        let mut solver = Solver::default();
+
+        solver
+            .add_rule(var("B").and(not(var("A"))).then_label("M"))
+            .unwrap();
+        solver
+            .add_rule(label("M").formula(var("D") + var("D") * var("E") / 10.0))
+            .unwrap();
+
+        assert_eq!(("M".into(), 4.5.into()), solve(&solver, &context));
+    }
+
+    #[test]
+    fn test_ast_rules() {
+        let context = variable_tables();
+        let mut solver = Solver::default();
+        let formula = |value| {
+            serde_json::json!({
+                "version": 1,
+                "rule": "arithmetic",
+                "labels": ["T"],
+                "outputs": [{"name": "K", "value": value}],
+            })
+        };
+        let rules = [
+            Source::Text("const RATE_X = 4".into()),
+            Source::Ast(formula(serde_json::json!({
+                "type": "binary",
+                "operator": "/",
+                "lhs": {"type": "variable", "name": "D"},
+                "rhs": {"type": "variable", "name": "RATE_X"},
+            }))),
+        ];
+
+        solver.add_all(&rules).unwrap();
+
+        assert_eq!(("T".into(), 0.375.into()), solve(&solver, &context));
+
+        // Rules which weren't written down have nothing to point at.
+        let rules = [Source::Ast(formula(serde_json::json!({
+            "type": "variable",
+            "name": "A",
+        })))];
+        let error = solver.add_all(&rules).unwrap_err();
+
+        assert_eq!(
+            "Type error: expected number, found bool",
+            format!("{}", error)
+        );
+    }
+
+    #[test]
+    fn test_conditional_expressions() {
+        let context = variable_tables();
+        let mut solver = Solver::default();
+
+        solver.add("H = T => K = if(B && E > F, D * 2, D)").unwrap();
+        let solution = solve(&solver, &context);
+
+        assert_eq!(("T".into(), 3.0.into()), solution);
+    }
+
+    #[test]
+    fn test_identifiers() {
+        let mut context = variable_tables();
+        let mut solver = Solver::default();
+
+        context
+            .variables
+            .insert("customer.is_vip".into(), true.into());
+        context.variables.insert("customer.age".into(), 42.0.into());
+        context.variables.insert("rate_2024".into(), 0.5.into());
+
+        solver
+            .add("customer.is_vip && customer.age > 30 => H = VIP_2024")
+            .unwrap();
+        solver.add("H = VIP_2024 => K = D * rate_2024").unwrap();
+        let solution = solve(&solver, &context);
+
+        assert_eq!(("VIP_2024".into(), 0.75.into()), solution);
+    }
+
+    #[test]
+    fn test_string_conditions() {
+        let mut context = variable_tables();
+        let mut solver = Solver::default();
+
+        context.variables.insert("country".into(), "AT".into());
+        context.variables.insert("plan".into(), "basic".into());
+
+        solver
+            .add(r#"country in ["DE", "AT", "CH"] && plan != "pro" => H = M"#)
+            .unwrap();
+        let solution = solve(&solver, &context);
+
+        assert_eq!(("M".into(), 4.5.into()), solution);
+    }
+
+    #[test]
+    fn test_decimal_arithmetic() {
+        let mut context = variable_tables();
+        let solver = Solver::default().with_arithmetic(Arithmetic::Decimal {
+            scale: 2,
+            rounding: Rounding::HalfEven,
+        });
+
+        context.variables.insert("D".into(), 19.99.into());
+        context.variables.insert("E".into(), 3.into());
+        context.variables.insert("A".into(), true.into());
+        context.variables.insert("C".into(), false.into());
+
+        let (label, result) = solve(&solver, &context);
+
+        // 19.99 + 5.997, where the latter is rounded to 6.00.
+        assert_eq!("M", label);
+        assert_eq!("25.99", result.to_string());
+    }
+
+    #[test]
+    fn test_decimal_literals() {
+        let context = variable_tables();
+        let decimal = Arithmetic::Decimal {
+            scale: 18,
+            rounding: Rounding::HalfEven,
+        };
+
+        // Literals are read from their spelling, which keeps digits a float
+        // would lose, whether or not constants are folded.
+        for optimize in [false, true] {
+            let mut solver = Solver::default()
+                .with_arithmetic(decimal)
+                .with_optimization(optimize);
+            solver
+                .add("H = T && 0.1 + 0.2 == 0.3 => K = 0.123456789012345678")
+                .unwrap();
+
+            let (label, result) = solve(&solver, &context);
+
+            assert_eq!("T", label);
+            assert_eq!("0.123456789012345678", result.to_string());
+        }
+    }
+
+    #[test]
+    fn test_exact_arithmetic() {
+        let mut context = variable_tables();
+        let mut solver = Solver::default();
+
+        context.variables.insert("D".into(), 0.1.into());
+        context.variables.insert("E".into(), 0.2.into());
+        solver.add("H = T => K = (D + E) * 3 - 0.9").unwrap();
+
+        let (label, exact) = solver.solve_exact(&context).unwrap();
+
+        assert_eq!("T", label);
+        assert_eq!(vec![("K".into(), Rational::from(0))], exact);
+
+        let check = solver.cross_check(&context).unwrap();
+
+        assert_eq!(exact, check.exact);
+        assert!(check.deviation > 0.0 && check.deviation < 1e-15);
+    }
+
+    #[test]
+    fn test_float_policy() {
+        let mut context = variable_tables();
+        let mut solver = Solver::default();
+
+        context.variables.insert("F".into(), 20.0.into());
+        solver.add("H = T => K = D / (E - F)").unwrap();
+
+        let (_, result) = solve(&solver, &context);
+
+        assert_eq!(Number::Float(f64::INFINITY), result);
+
+        let solver = solver.with_float_policy(FloatPolicy::DivisionByZero);
+        let error = solver.solve(&context).unwrap_err();
+
+        assert_eq!(
+            "Division by zero in D / (E - F) where D = 1.5, E = 20.0, F = 20.0",
+            format!("{}", error)
+        );
+    }
+
+    #[test]
+    fn test_precision() {
+        let context = variable_tables();
+        let mut solver = Solver::default().with_precision(Some(Precision {
+            digits: 1,
+            rounding: Rounding::HalfUp,
+        }));
+
+        // 1.5 - 1.5 * 10 / 30 = 1.0
+        assert_eq!(("T".into(), 1.0.into()), solve(&solver, &context));
+
+        solver.add("H = T => K = D / 4").unwrap();
+        let (_, result) = solve(&solver, &context);
+
+        assert_eq!("0.4", result.to_string());
+
+        // A directive replaces the precision the solver is made with.
+        solver.add("precision 2").unwrap();
+        let (_, result) = solve(&solver, &context);
+
+        assert_eq!("0.38", result.to_string());
+
+        // The precision of the rule takes precedence.
+        solver.add("H = T => K[3, half_even] = D / 16").unwrap();
+        let (_, result) = solve(&solver, &context);
+
+        assert_eq!("0.094", result.to_string());
+    }
+
+    #[test]
+    fn test_numeric_literals() {
+        let context = variable_tables();
+        let mut solver = Solver::default();
+
+        solver
+            .add("H = T => K = D * 12.5% + 1_000 - 0x10 * 1e-3")
+            .unwrap();
+        let (_, result) = solve(&solver, &context);
+
+        assert_eq!(Number::Float(1000.171500), result);
+
+        let error = solver.add("H = T => K = D * 0x1G").unwrap_err();
+        assert_eq!(
+            "Malformed number at 17..21 (0x1G): invalid hexadecimal digit G",
+            format!("{}", error)
+        );
+    }
+
+    #[test]
+    fn test_constants() {
+        let mut context = variable_tables();
+        let mut solver = Solver::default();
+
+        solver.add("const RATE_T = RATE_M * 2").unwrap();
+        let (_, result) = solve(&solver, &context);
+
+        // 1.5 - 1.5 * 10 / 20
+        assert_eq!(Number::Float(0.75), result);
+
+        // Request variables shadow constants.
+        context.variables.insert("RATE_T".into(), 5.into());
+        let (_, result) = solve(&solver, &context);
+
+        assert_eq!(Number::Float(-1.5), result);
+
+        let error = solver.add("const RATE_Q = D * 2").unwrap_err();
+        assert_eq!("Variable D is undefined", format!("{}", error));
+    }
+
+    #[test]
+    fn test_label_matching() {
+        let context = variable_tables();
+        let mut solver = Solver::default();
+
+        solver.add("!A && B => H = X").unwrap();
+        solver.add("H = * => K = 0").unwrap();
+        solver.add("H = Y | Z => K = 1").unwrap();
+
+        assert_eq!(("X".into(), 0.into()), solve(&solver, &context));
+
+        // Labels take precedence over the wildcard, regardless of order.
+        solver.add("H = X | Y => K = D").unwrap();
+        solver.add("H = * => K = 2").unwrap();
+
+        assert_eq!(("X".into(), 1.5.into()), solve(&solver, &context));
+    }
+
+    #[test]
+    fn test_guards() {
+        let mut context = variable_tables();
+        let mut solver = Solver::default();
+        solver.declare(&context);
+
+        solver.add("H = T && D > 100 => K = D * 0.9").unwrap();
+        solver.add("H = T && D > 1000 => K = D * 0.8").unwrap();
+
+        context.variables.insert("D".into(), 200.into());
+        assert_eq!(("T".into(), 180.0.into()), solve(&solver, &context));
+
+        context.variables.insert("D".into(), 2000.into());
+        assert_eq!(("T".into(), 1600.0.into()), solve(&solver, &context));
+
+        // The base formula applies when no guard passes.
+        context.variables.insert("D".into(), 3.into());
+        assert_eq!(("T".into(), 2.0.into()), solve(&solver, &context));
+
+        // The guard is the rest of the clause, so the rule applies to M
+        // only, even though B holds.
+        solver.add("H = M && D > 1000 || B => K = 7").unwrap();
+        assert_eq!(("T".into(), 2.0.into()), solve(&solver, &context));
+
+        solver.add("H = T && D > 1000 || B => K = 7").unwrap();
+        assert_eq!(("T".into(), 7.into()), solve(&solver, &context));
+
+        let error = solver.add("H = T && D => K = D").unwrap_err();
+        assert_eq!(
+            "Type error at 9..10 (D): expected bool, found number",
+            format!("{}", error)
+        );
+    }
+
+    #[test]
+    fn test_multiple_outputs() {
+        let context = variable_tables();
+        let mut solver = Solver::default().with_precision(Some(Precision {
+            digits: 2,
+            rounding: Rounding::HalfEven,
+        }));
+
+        solver
+            .add("H = T => net = D * 3, tax = net * 0.19, gross = net + tax")
+            .unwrap();
+        let solution = solver.solve(&context).unwrap();
+
+        // Later outputs see the rounded earlier ones, 0.855 -> 0.86.
+        assert_eq!(
+            vec![
+                ("net".into(), 4.5.into()),
+                ("tax".into(), 0.86.into()),
+                ("gross".into(), 5.36.into()),
+            ],
+            solution.outputs
+        );
+
+        let (_, exact) = solver.solve_exact(&context).unwrap();
+
+        assert_eq!(Rational::from_number(5.355.into()).unwrap(), exact[2].1);
+
+        // Outputs can't refer to the ones after them.
+        solver.add("H = T => K = tax, tax = D").unwrap();
+        let error = solver.solve(&context).unwrap_err();
+
+        assert_eq!("Variable tax is undefined", format!("{}", error));
+    }
+
+    #[test]
+    fn test_conditional_outputs() {
+        let context = variable_tables();
+        let mut solver = Solver::default();
+
+        solver
+            .add("H = T => net = D, K = if(net > 1, net, 0)")
+            .unwrap();
+
+        assert_eq!(("T".into(), 1.5.into()), solve(&solver, &context));
+
+        let (_, exact) = solver.solve_exact(&context).unwrap();
+        assert_eq!(Rational::from_number(1.5.into()).unwrap(), exact[1].1);
+
+        let check = solver.cross_check(&context).unwrap();
+        assert_eq!(0.0, check.deviation);
+    }
+
+    #[test]
+    fn test_type_errors() {
+        let mut solver = Solver::default();
+        solver.declare(&variable_tables());
+
+        let error = solver.add("H = M => K = A + 1").unwrap_err();
+        assert_eq!(
+            "Type error at 13..14 (A): expected number, found bool",
+            format!("{}", error)
+        );
+
+        let error = solver.add("D && B => H = M").unwrap_err();
+        assert_eq!(
+            "Type error at 0..1 (D): expected bool, found number",
+            format!("{}", error)
+        );
+
+        let error = solver.add(r#"H = M => K = if(A, "a", "b")"#).unwrap_err();
+        assert_eq!(
+            r#"Type error at 19..22 ("a"): expected number, found string"#,
+            format!("{}", error)
+        );
+
+        solver.add("E > D && B => H = M").unwrap();
+
+        let error = solver.add("A + 1 in [] => H = M").unwrap_err();
+        assert_eq!(
+            "Type error at 0..1 (A): expected number, found bool",
+            format!("{}", error)
+        );
+
+        // The base variables are typed even if nothing is declared.
+        let error = Solver::default().add("H = M => K = A + 1").unwrap_err();
+        assert_eq!(
+            "Type error at 13..14 (A): expected number, found bool",
+            format!("{}", error)
+        );
+    }
+
+    #[test]
+    fn test_default_rule() {
+        let mut context = variable_tables();
+        let mut solver = Solver::default();
+
+        solver.add("else => H = NONE").unwrap();
+        solver.add("H = NONE => K = 0").unwrap();
+
+        assert!(!solver.solve(&context).unwrap().default);
+
+        context.variables.insert("B".into(), false.into());
+        let solution = solver.solve(&context).unwrap();
+
+        assert_eq!(
+            Solution {
+                label: "NONE".into(),
+                outputs: vec![("K".into(), 0.into())],
+                default: true,
+            },
+            solution
+        );
+    }
+
+    #[test]
+    fn test_partially_known_context() {
+        let mut context = variable_tables();
+        let mut solver = Solver::default();
+
+        context.variables.remove("A");
+        let possibilities = solver.solve_partially(&context).unwrap();
+
+        // Either T or P, depending on A.
+        assert_eq!(vec!["T", "P"], possibilities.possible);
+        assert_eq!(None, possibilities.certain);
+
+        context.variables.insert("C".into(), false.into());
+        let possibilities = solver.solve_partially(&context).unwrap();
+
+        assert_eq!(vec!["M"], possibilities.possible);
+        assert_eq!(Some("M".into()), possibilities.certain);
+
+        // The default rule stays possible while no rule is certainly true.
+        context.variables.remove("B");
+        solver.add("else => H = NONE").unwrap();
+        let possibilities = solver.solve_partially(&context).unwrap();
+
+        assert_eq!(vec!["M", "NONE"], possibilities.possible);
+    }
+
+    #[test]
+    fn test_residual_formulas() {
+        let mut context = variable_tables();
+        let mut solver = Solver::default();
+
+        context.variables.insert("A".into(), true.into());
+        context.variables.insert("C".into(), false.into());
+        context.variables.insert("D".into(), 2.into());
+        context.variables.remove("E");
+
+        let (label, outputs) = solver.solve_residual(&context).unwrap();
+
+        assert_eq!("M", label);
+        assert_eq!("2 + 0.2 * E", outputs[0].1.to_string());
+
+        solver
+            .add("H = M => net = D * E, tax = net * 0.5, gross = D + tax")
+            .unwrap();
+        let (_, outputs) = solver.solve_residual(&context).unwrap();
+        let outputs: Vec<String> = outputs
+            .iter()
+            .map(|(_, output)| output.to_string())
+            .collect();
+
+        assert_eq!(vec!["2 * E", "0.5 * net", "2 + tax"], outputs);
+    }
+
+    #[test]
+    fn test_optimization() {
+        let mut context = variable_tables();
+        let mut solver = Solver::default().with_optimization(true);
+
+        solver.add("B || !B => H = M").unwrap();
+        solver
+            .add("H = M && true => K = D * (1 + 1) * 1 + 0")
+            .unwrap();
+
+        assert_eq!(("M".into(), 3.0.into()), solve(&solver, &context));
+
+        let (expression, _) = solver.rules().boolean.last().unwrap();
+        let output = &solver.rules().arithmetic.last().unwrap().outputs[0];
+
+        assert_eq!("true", expression.to_string());
+        assert_eq!("D * 2", output.expression.to_string());
+        assert_eq!(None, solver.rules().arithmetic.last().unwrap().guard);
+
+        // The operand is no longer evaluated.
+        context.variables.remove("B");
+        assert_eq!(("M".into(), 3.0.into()), solve(&solver, &context));
+    }
+
+    #[test]
+    fn test_optimization_order() {
+        let mut context = variable_tables();
+        let decimal = Arithmetic::Decimal {
+            scale: 2,
+            rounding: Rounding::HalfEven,
+        };
+        let formula = "H = M => K = D * (1 / 3 * 3)";
+
+        context.variables.insert("A".into(), true.into());
+        context.variables.insert("C".into(), false.into());
+
+        let mut expected = Solver::default().with_arithmetic(decimal);
+        expected.add(formula).unwrap();
+
+        // Constants are folded anew once the arithmetic changes, 1 / 3 * 3
+        // being 0.99 rather than 1.
+        let mut solver = Solver::default().with_optimization(true);
+        solver.add(formula).unwrap();
+        let solver = solver.with_arithmetic(decimal);
+        let output = &solver.rules().arithmetic.last().unwrap().outputs[0];
 
-        assert_eq!(("M".into(), 0.05), solution);
+        assert_eq!("D * 0.99", output.expression.to_string());
+        assert_eq!(solve(&expected, &context), solve(&solver, &context));
+        assert_eq!("1.48", solve(&solver, &context).1.to_string());
     }
 
     #[test]
     fn test_unsolvable() {
-        let (mut bool_vars, arithmetic_vars) = variable_tables();
+        let mut context = variable_tables();
         let solver = Solver::default();
 
-        bool_vars.insert("B".into(), false);
+        context.variables.insert("B".into(), false.into());
 
-        let solution = solver.solve(bool_vars, arithmetic_vars).unwrap_err();
+        let solution = solver.solve(&context).unwrap_err();
 
         assert_eq!("Unable to find the solution", format!("{}", solution));
     }